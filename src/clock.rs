@@ -0,0 +1,78 @@
+/*
+ * Author: Dylan Turner
+ * Description: Turns real time into a whole number of fixed length game ticks
+ */
+
+use std::time::{
+    Instant, Duration
+};
+use crate::game::TICKS_PER_SEC;
+
+// A slow frame runs the ticks it missed, but never more than this many at once
+const MAX_CATCH_UP_TICKS: u64 = 15;
+
+pub struct Clock {
+    last: Instant,
+    behind: Duration, // Game time that has passed but isn't a whole tick yet
+    speed: f64 // Game time per real time
+}
+
+impl Clock {
+    pub fn tick_length() -> Duration {
+        Duration::from_nanos(1_000_000_000 / TICKS_PER_SEC)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            last: Instant::now(),
+            behind: Duration::ZERO,
+            speed: 1.0
+        }
+    }
+
+    // Forget about time spent while the game wasn't running, e.g. paused
+    pub fn reset(&mut self) {
+        self.last = Instant::now();
+        self.behind = Duration::ZERO;
+    }
+
+    // Run faster or slower than real time, e.g. 0.5 for half speed. Must be above 0
+    pub fn set_speed(&mut self, speed: f64) {
+        assert!(speed > 0.0 && speed.is_finite(), "Clock speed must be above 0, not {}", speed);
+        self.catch_up(); // Time so far counts at the old speed, and its ticks are still due
+        self.speed = speed;
+    }
+
+    // Add the game time since the last check to what's behind
+    fn catch_up(&mut self) {
+        let now = Instant::now();
+        self.behind += now.duration_since(self.last).mul_f64(self.speed);
+        self.last = now;
+    }
+
+    // How many ticks should run now to catch up with real time
+    pub fn ticks_due(&mut self) -> u64 {
+        self.catch_up();
+        let tick = Clock::tick_length();
+        let ticks = self.behind.as_nanos() / tick.as_nanos();
+        if ticks > MAX_CATCH_UP_TICKS as u128 {
+            self.behind = Duration::ZERO;
+            return MAX_CATCH_UP_TICKS;
+        }
+        self.behind -= tick * ticks as u32;
+        ticks as u64
+    }
+
+    // Real time left until the next tick is due
+    pub fn until_next_tick(&self) -> Duration {
+        let game_time = self.behind + self.last.elapsed().mul_f64(self.speed);
+        let left = Clock::tick_length().saturating_sub(game_time);
+        Duration::try_from_secs_f64(left.as_secs_f64() / self.speed).unwrap_or(Duration::MAX)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new()
+    }
+}
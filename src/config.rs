@@ -0,0 +1,342 @@
+/*
+ * Author: Dylan Turner
+ * Description: Player settings for front ends, stored in the config file next to the save data
+ */
+
+use std::str::FromStr;
+use serde::{
+    de, Serialize, Serializer, Deserialize, Deserializer
+};
+use crate::game::Input;
+
+// Which side of a wide terminal the game sits on
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    Left,
+    #[default]
+    Center,
+    Right
+}
+
+impl Anchor {
+    // Column offset to draw something width wide at in a terminal term_width wide
+    pub fn offset(self, width: u16, term_width: u16) -> u16 {
+        let space = term_width.saturating_sub(width);
+        match self {
+            Anchor::Left => 0,
+            Anchor::Center => space / 2,
+            Anchor::Right => space
+        }
+    }
+}
+
+// How the game is drawn. Stored in the config file under [display]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Display {
+    pub ghost: bool, // Show where the falling piece will land
+    pub ghost_glyph: String, // Drawn for each cell of the ghost. Should be two columns wide
+    pub anchor: Anchor
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self {
+            ghost: true,
+            ghost_glyph: String::from("░░"),
+            anchor: Anchor::default()
+        }
+    }
+}
+
+// Things a player can do with a key
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Left,
+    Right,
+    RotateLeft,
+    RotateRight,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    Pause,
+    Quit,
+    Start,
+    Scores
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::Left, Action::Right, Action::RotateLeft, Action::RotateRight,
+        Action::SoftDrop, Action::HardDrop, Action::Hold, Action::Pause, Action::Quit,
+        Action::Start, Action::Scores
+    ];
+    pub const IN_GAME: [Action; 9] = [
+        Action::Left, Action::Right, Action::RotateLeft, Action::RotateRight,
+        Action::SoftDrop, Action::HardDrop, Action::Hold, Action::Pause, Action::Quit
+    ];
+    pub const MENU: [Action; 2] = [ Action::Start, Action::Scores ]; // Only used on the menu
+
+    // Short description for menus and help text
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::RotateLeft => "Rotate L",
+            Action::RotateRight => "Rotate R",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::Quit => "Quit",
+            Action::Start => "Start",
+            Action::Scores => "High scores"
+        }
+    }
+
+    // What the game does for this action. Pause, quit and the menu are up to the front end
+    pub fn input(self) -> Option<Input> {
+        match self {
+            Action::Left => Some(Input::Left),
+            Action::Right => Some(Input::Right),
+            Action::RotateLeft => Some(Input::RotateLeft),
+            Action::RotateRight => Some(Input::RotateRight),
+            Action::SoftDrop => Some(Input::SoftDrop),
+            Action::HardDrop => Some(Input::HardDrop),
+            Action::Hold => Some(Input::Hold),
+            Action::Pause | Action::Quit | Action::Start | Action::Scores => None
+        }
+    }
+}
+
+// A key press, with escape sequences already decoded. Written by name in the config file,
+// e.g. "a", "space", "left", "f5" or "ctrl+c"
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Alt(char),
+    F(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Backspace,
+    Enter,
+    Tab,
+    BackTab,
+    Esc
+}
+
+impl Key {
+    const NAMED: [(&'static str, Key); 16] = [
+        ("space", Key::Char(' ')), ("up", Key::Up), ("down", Key::Down), ("left", Key::Left),
+        ("right", Key::Right), ("home", Key::Home), ("end", Key::End),
+        ("pageup", Key::PageUp), ("pagedown", Key::PageDown), ("insert", Key::Insert),
+        ("delete", Key::Delete), ("backspace", Key::Backspace), ("enter", Key::Enter),
+        ("tab", Key::Tab), ("backtab", Key::BackTab), ("esc", Key::Esc)
+    ];
+
+    pub fn name(self) -> String {
+        if let Some((name, _)) = Key::NAMED.iter().find(|(_, key)| *key == self) {
+            return String::from(*name);
+        }
+        match self {
+            Key::Ctrl(c) => format!("ctrl+{}", c),
+            Key::Alt(c) => format!("alt+{}", c),
+            Key::F(n) => format!("f{}", n),
+            Key::Char(c) => String::from(c),
+            _ => String::new() // All the others are named
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        if let Some((_, key)) = Key::NAMED.iter().find(|(name, _)| *name == lower) {
+            return Ok(*key);
+        }
+
+        // Single characters keep their case so "A" (shift+a) and "a" are different keys
+        let single = |rest: &str| {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if !c.is_control() => Some(c),
+                _ => None
+            }
+        };
+        let key = if let Some(rest) = lower.strip_prefix("ctrl+") {
+            single(rest).map(Key::Ctrl)
+        } else if lower.starts_with("alt+") {
+            single(&s[4..]).map(Key::Alt) // Alt keeps the case like plain characters
+        } else if let Some(num) = lower.strip_prefix('f').filter(|num| !num.is_empty()) {
+            num.parse().ok().filter(|n| (1..=12).contains(n)).map(Key::F)
+        } else {
+            single(s).map(Key::Char)
+        };
+        key.ok_or_else(|| format!("Unknown key '{}'", s))
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+// Which keys do what. Stored in the config file under [keys]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub left: Vec<Key>,
+    pub right: Vec<Key>,
+    pub rotate_left: Vec<Key>,
+    pub rotate_right: Vec<Key>,
+    pub soft_drop: Vec<Key>,
+    pub hard_drop: Vec<Key>,
+    pub hold: Vec<Key>,
+    pub pause: Vec<Key>,
+    pub quit: Vec<Key>,
+    pub start: Vec<Key>,
+    pub scores: Vec<Key>
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[Key] {
+        match action {
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+            Action::RotateLeft => &self.rotate_left,
+            Action::RotateRight => &self.rotate_right,
+            Action::SoftDrop => &self.soft_drop,
+            Action::HardDrop => &self.hard_drop,
+            Action::Hold => &self.hold,
+            Action::Pause => &self.pause,
+            Action::Quit => &self.quit,
+            Action::Start => &self.start,
+            Action::Scores => &self.scores
+        }
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| self.keys(*action).contains(&key))
+    }
+
+    // Key names joined for display, e.g. "a/h"
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self.keys(action).iter().map(|key| key.name()).collect();
+        names.join("/")
+    }
+
+    // Every key bound to more than one action, as messages for the player
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, first) in Action::ALL.iter().enumerate() {
+            for second in &Action::ALL[i + 1..] {
+                for key in self.keys(*first) {
+                    if self.keys(*second).contains(key) {
+                        conflicts.push(format!(
+                            "'{}' is bound to both {} and {}",
+                            key.name(), first.label(), second.label()
+                        ));
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            left: vec![ Key::Char('a'), Key::Left ],
+            right: vec![ Key::Char('d'), Key::Right ],
+            rotate_left: vec![ Key::Char('q') ],
+            rotate_right: vec![ Key::Char('e'), Key::Up ],
+            soft_drop: vec![ Key::Char('s'), Key::Down ],
+            hard_drop: vec![ Key::Char(' ') ],
+            hold: vec![ Key::Char('w') ],
+            pause: vec![ Key::Char('p'), Key::Esc ],
+            quit: vec![ Key::Backspace ],
+            start: vec![ Key::Enter ],
+            scores: vec![ Key::Tab ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> Result<Key, String> {
+        name.parse()
+    }
+
+    #[test]
+    fn characters_keep_their_case() {
+        assert_eq!(parse("a"), Ok(Key::Char('a')));
+        assert_eq!(parse("A"), Ok(Key::Char('A')));
+        assert_eq!(parse("alt+X"), Ok(Key::Alt('X')));
+        assert_eq!(parse("alt+x"), Ok(Key::Alt('x')));
+        assert_eq!(parse("ctrl+C"), Ok(Key::Ctrl('c'))); // Terminals can't tell these apart
+    }
+
+    #[test]
+    fn f_on_its_own_is_a_character() {
+        assert_eq!(parse("f"), Ok(Key::Char('f')));
+        assert_eq!(parse("F"), Ok(Key::Char('F')));
+        assert_eq!(parse("f1"), Ok(Key::F(1)));
+        assert_eq!(parse("F12"), Ok(Key::F(12)));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for name in [ "f13", "f0", "nokey", "ctrl+", "alt+ab", "" ] {
+            assert_eq!(parse(name), Err(format!("Unknown key '{}'", name)));
+        }
+    }
+
+    #[test]
+    fn names_come_back_as_the_same_key() {
+        let others = [ Key::Char('A'), Key::Ctrl('c'), Key::Alt('X'), Key::F(7) ];
+        for key in Key::NAMED.iter().map(|(_, key)| *key).chain(others) {
+            assert_eq!(parse(&key.name()), Ok(key));
+        }
+        for (name, key) in Key::NAMED {
+            assert_eq!(parse(&name.to_uppercase()), Ok(key)); // Names aren't case sensitive
+        }
+    }
+
+    #[test]
+    fn bindings_save_keys_by_name() {
+        let keys = KeyBindings {
+            hold: vec![ Key::Char('W'), Key::Alt('x'), Key::F(2) ],
+            ..KeyBindings::default()
+        };
+        let text = toml::to_string(&keys).unwrap();
+        assert!(text.contains(r#"hold = ["W", "alt+x", "f2"]"#));
+        assert!(text.contains(r#"hard_drop = ["space"]"#));
+        assert_eq!(toml::from_str::<KeyBindings>(&text), Ok(keys));
+
+        let err = toml::from_str::<KeyBindings>(r#"hold = ["nokey"]"#).unwrap_err();
+        assert!(err.to_string().contains("Unknown key 'nokey'"));
+    }
+}
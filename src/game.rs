@@ -1,226 +1,543 @@
 /*
  * Author: Dylan Turner
- * Description: Main game loop
+ * Description: Game rules and state, independent of any terminal
  */
 
-use std::{
-    thread::sleep,
-    time::{
-        Instant, Duration
-    }
-};
-use termion::color::{
-    Color, White, Reset
-};
-use math::round::floor;
-use crate::io::{
-    Canvas, KeyReader,
-    DISP_HEIGHT, GRID_WIDTH, GRID_HEIGHT, SHAPE_WIDTH, SHAPE_STR
+use std::collections::VecDeque;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{
+    Serialize, Deserialize
 };
-use crate::tetromino::{
-    SHAPE_COLORS, Tetromino
+use crate::{
+    randomizer::{
+        Randomizer, RandomizerKind
+    }, tetromino::{
+        Rotation, ShapeType, Tetromino
+    }
 };
 
-pub const FPS: u64 = 60;
-const BORDER: [&'static str; DISP_HEIGHT as usize] = [
-    "H:                    ",
-    "S:                    ",
-    "╔════════════════════╗",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "║                    ║",
-    "╚════════════════════╝"
-];
-const PAUSE: [&'static str; 3 as usize] = [
-    "╔════════╗",
-    "║ PAUSED ║",
-    "╚════════╝"
+pub const MIN_WIDTH: usize = 4; // Narrowest well the I piece can spawn in
+pub const MAX_WIDTH: usize = 50;
+pub const MIN_HEIGHT: usize = 4;
+pub const MAX_HEIGHT: usize = 100;
+pub const BUFFER_HEIGHT: usize = 20; // Hidden rows above the grid that pieces spawn into
+pub const MIN_PREVIEW: usize = 1;
+pub const MAX_PREVIEW: usize = 6;
+pub const TICKS_PER_SEC: u64 = 60;
+pub const GRAVITY_UNIT: u32 = 65_536; // Gravity is counted in 1/65536ths of a cell per tick
+pub const MAX_START_LEVEL: u64 = 15;
+const MAX_LOCK_RESETS: u32 = 15;
+const GIVEN_BLOCK: i8 = ShapeType::Square as i8; // Blocks from with_rows, colored like O's
+
+/*
+ * Gravity for levels 1 to 20 from the guideline curve of (0.8 - (level - 1) * 0.007)^(level - 1)
+ * seconds per row, in GRAVITY_UNITs per tick. Worked out ahead of time so every machine falls
+ * exactly the same way. Levels past the end stay at the fastest speed
+ */
+const LEVEL_GRAVITY: [u32; 20] = [
+    1_092, 1_377, 1_768, 2_310, 3_075, 4_168, 5_758, 8_106, 11_634, 17_026,
+    25_415, 38_708, 60_168, 95_483, 154_742, 256_186, 433_424, 749_596, 1_325_716, 2_398_489
 ];
-const BORDER_COLOR: &dyn Color = &White;
-const SCORE_COLOR: &dyn Color = &White;
-const SHAPE_DRAW_OFFSET: i16 = 5;
-const INITIAL_FALL_SPD: f32 = 0.9;
-const LAND_TIME_DELAY_S: f64 = 0.1;
-const SPD_INC: f32 = 0.05;
-const ROW_SCORE_INC: u64 = 100;
-const PIECE_SCORE_INC: u64 = 100;
-const PIECE_SCORE_FAL_MULT: f32 = 50.0;
-
-#[derive(PartialEq, Clone, Copy)]
+const LINE_SCORES: [u64; 5] = [ 0, 100, 300, 500, 800 ]; // By rows cleared, times level
+const T_SPIN_SCORES: [u64; 4] = [ 400, 800, 1_200, 1_600 ];
+const MINI_T_SPIN_SCORES: [u64; 3] = [ 100, 200, 400 ];
+const COMBO_SCORE: u64 = 50; // Times combo count and level
+const FULL_T_SPIN_KICK: usize = 4; // The last SRS kick always counts as a full T-spin
+const SPAWN_ROW: i16 = -1; // Flat side down in the lowest buffer row
+const SOFT_DROP_MULT: u32 = 20;
+// Terminals don't send key releases, so a press counts as held for long enough to outlast the
+// wait before a held key starts repeating (250-660 ms on most systems). A tap drops for that long
+const SOFT_DROP_HOLD_TICKS: u64 = 40;
+const SOFT_DROP_SCORE: u64 = 1; // Per row
+const HARD_DROP_SCORE: u64 = 2; // Per row
+
+/*
+ * Grid of settled blocks. -1 is empty, otherwise the shape index it came from.
+ * The hidden buffer rows come first, so row y of the grid is at index y + BUFFER_HEIGHT
+ * and rows above the grid have negative y everywhere else
+ */
+pub type Board = Vec<Vec<i8>>;
+
+// Options that change how a game plays out. Stored in the config file under [rules]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Ruleset {
+    pub randomizer: RandomizerKind,
+    pub width: usize, // Size of the well in cells, MIN_WIDTH to MAX_WIDTH
+    pub height: usize, // Visible rows, MIN_HEIGHT to MAX_HEIGHT
+    pub preview: usize, // How many upcoming pieces can be seen, 1 to 6
+    pub lock_delay_ms: u64, // Time on the ground before a piece locks
+    pub start_level: u64, // 1 to MAX_START_LEVEL
+    pub lines_per_level: u64
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self {
+            randomizer: RandomizerKind::default(),
+            width: 10,
+            height: 20,
+            preview: 5,
+            lock_delay_ms: 500,
+            start_level: 1,
+            lines_per_level: 10
+        }
+    }
+}
+
+impl Ruleset {
+    // The same rules with every option brought into its allowed range
+    pub fn clamped(mut self) -> Self {
+        self.width = self.width.clamp(MIN_WIDTH, MAX_WIDTH);
+        self.height = self.height.clamp(MIN_HEIGHT, MAX_HEIGHT);
+        self.preview = self.preview.clamp(MIN_PREVIEW, MAX_PREVIEW);
+        self.start_level = self.start_level.clamp(1, MAX_START_LEVEL);
+        self.lines_per_level = self.lines_per_level.max(1);
+        self
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Dir {
     Down,
     Left,
     Right
 }
 
-pub struct GameState {
-    score: u64,
-    curr_shape: Tetromino,
-    fall_spd: f32,
-    blocks: [[i8; GRID_WIDTH]; GRID_HEIGHT],
-    land_timer: f64
+// Player actions a front end can feed into the game
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Input {
+    Left,
+    Right,
+    RotateLeft,
+    RotateRight,
+    SoftDrop,
+    HardDrop,
+    Hold
 }
 
-enum UpdateEndState {
-    Quit,
-    Lost,
+// T-spins found with the three-corner rule when a T locks
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Spin {
+    None,
+    Mini,
+    Full
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum UpdateEndState {
     Continue,
-    Pause
+    Lost
+}
+
+pub struct GameState {
+    seed: u64,
+    rng: ChaCha8Rng, // Owned by the game so a seed always gives the same pieces
+    randomizer: Box<dyn Randomizer>,
+    rules: Ruleset,
+    score: u64,
+    level: u64,
+    lines: u64,
+    combo: Option<u64>, // Locks in a row that cleared rows after the first, None if the last didn't
+    back_to_back: bool, // Last clear was a tetris or T-spin
+    curr_shape: Tetromino,
+    queue: VecDeque<Tetromino>,
+    hold: Option<Tetromino>,
+    can_hold: bool, // Only one hold per piece
+    gravity: u32,
+    gravity_acc: u32, // Progress towards falling the next cell, in GRAVITY_UNITs
+    blocks: Board,
+    lock_timer: u64, // Ticks left on the ground before locking
+    lock_resets: u32, // Moves and turns that have restarted the lock timer
+    lowest_row: i16, // Reaching a new lowest row gives the piece its resets back
+    last_kick: Option<usize>, // Set when the last thing the piece did was turn
+    soft_drop_ticks: u64, // Time left before soft drop counts as released
+    ticks: u64 // Updates so far
 }
 
 impl GameState {
-    pub fn new() -> Self {
-        Self {
+    pub fn new(seed: u64, rules: Ruleset) -> Self {
+        let rules = rules.clamped();
+
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut randomizer = rules.randomizer.create();
+        let curr_shape = Tetromino::new(randomizer.next(&mut rng));
+        let queue = (0..rules.preview).map(|_| Tetromino::new(randomizer.next(&mut rng))).collect();
+        let level = rules.start_level;
+        let blocks = vec![ vec![ -1; rules.width ]; BUFFER_HEIGHT + rules.height ];
+        let mut state = Self {
+            seed,
+            rng,
+            randomizer,
+            rules,
             score: 0,
-            curr_shape: Tetromino::select(),
-            fall_spd: INITIAL_FALL_SPD,
-            blocks: [
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ],
-                [ -1, -1, -1, -1, -1, -1, -1, -1, -1, -1 ]
-            ], land_timer: LAND_TIME_DELAY_S
-        }
+            level,
+            lines: 0,
+            combo: None,
+            back_to_back: false,
+            curr_shape,
+            queue,
+            hold: None,
+            can_hold: true,
+            gravity: level_gravity(level),
+            gravity_acc: 0,
+            blocks,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_row: 0,
+            last_kick: None,
+            soft_drop_ticks: 0,
+            ticks: 0
+        };
+        state.bring_out(curr_shape); // Can't be blocked on an empty board
+        state
     }
 
-    pub fn play(&mut self, cnv: &mut Canvas, inp: &mut KeyReader, hs_disp: &Vec<&String>) -> u64 {
-        let mut last_time = Instant::now();
-        let interval_ms = 1_000 / FPS;
-        loop {
-            // Keep stable fps
-            let now = Instant::now();
-            let delta_time_ms = (now.duration_since(last_time).subsec_nanos() / 1_000_000) as u64;
-            if delta_time_ms < interval_ms {
-                sleep(Duration::from_millis(interval_ms - delta_time_ms));
-                continue;
-            }
-            last_time = now;
-
-            match self.update(inp, delta_time_ms) {
-                UpdateEndState::Continue => {},
-                UpdateEndState::Quit => {
-                    return 0;
-                }, UpdateEndState::Lost => {
-                    break;
-                },
-                UpdateEndState::Pause => {
-                    // Keep the game paused until 'p' is pressed again
-                    while inp.get_key() != b'p' {
-                        cnv.draw_strs(&PAUSE.to_vec(), (7, 13), BORDER_COLOR, &Reset); // Drawing the pause text
-                        sleep(Duration::from_millis(interval_ms));
-                    }
+    // A game whose well starts with its bottom rows filled in, e.g. to practice a setup.
+    // Rows go top to bottom with one character per column: '#' is a block and anything else
+    // is empty. Characters past the width of the well are ignored, and if there are more rows
+    // than the well is tall only the bottom ones are used
+    pub fn with_rows(seed: u64, rules: Ruleset, rows: &[&str]) -> Self {
+        let mut state = GameState::new(seed, rules);
+        let rows = &rows[rows.len().saturating_sub(state.rules.height)..]; // Keep the bottom
+        let first = state.blocks.len() - rows.len();
+        for (row, text) in state.blocks[first..].iter_mut().zip(rows) {
+            for (block, c) in row.iter_mut().zip(text.chars()) {
+                if c == '#' {
+                    *block = GIVEN_BLOCK;
                 }
             }
-            self.draw(cnv, hs_disp);
         }
 
+        // The first piece came out over an empty well, so bring it out again onto the rows.
+        // It always fits, since they don't reach the hidden rows it spawns in
+        let shape = Tetromino::new(state.curr_shape.shape);
+        state.bring_out(shape);
+        state
+    }
+
+    // How many times update has run, i.e. the game's age in ticks
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rules(&self) -> &Ruleset {
+        &self.rules
+    }
+
+    pub fn score(&self) -> u64 {
         self.score
     }
-    
-    fn update(&mut self, inp: &mut KeyReader, delta_time_ms: u64) -> UpdateEndState {
-        let key = inp.get_key();
-        match key {
-            127 => return UpdateEndState::Quit, // Backspace -> back to menu
-            b'p' => return UpdateEndState::Pause, // p -> Pause
-            b'a' => {
-                if self.can_move_curr_shape(Dir::Left) {
-                    self.curr_shape.pos.0 -= 1.0;
-                }
-            }, b'd' => {
-                if self.can_move_curr_shape(Dir::Right) {
-                    self.curr_shape.pos.0 += 1.0;
-                }
-            }, b'q' => {
-                if self.can_rotate_curr_shape(Dir::Left) {
-                    self.curr_shape.rotate(Dir::Left);
-                }
-            }, b'e' => {
-                if self.can_rotate_curr_shape(Dir::Right) {
-                    self.curr_shape.rotate(Dir::Right);
+
+    pub fn level(&self) -> u64 {
+        self.level
+    }
+
+    // Rows cleared so far
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn combo(&self) -> Option<u64> {
+        self.combo
+    }
+
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    // Size of the well in cells, not counting the buffer
+    pub fn width(&self) -> usize {
+        self.rules.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rules.height
+    }
+
+    pub fn blocks(&self) -> &Board {
+        &self.blocks
+    }
+
+    pub fn curr_shape(&self) -> &Tetromino {
+        &self.curr_shape
+    }
+
+    // Upcoming pieces, next one first
+    pub fn queue(&self) -> &VecDeque<Tetromino> {
+        &self.queue
+    }
+
+    pub fn hold(&self) -> Option<&Tetromino> {
+        self.hold.as_ref()
+    }
+
+    pub fn can_hold(&self) -> bool {
+        self.can_hold
+    }
+
+    // Grid cells covered by the falling piece. May be above the grid (y < 0)
+    pub fn curr_shape_cells(&self) -> [(i16, i16); 4] {
+        self.curr_shape.cells()
+    }
+
+    // Cells the falling piece would land on if dropped now
+    pub fn ghost_cells(&self) -> [(i16, i16); 4] {
+        let mut dy = 0;
+        while self.fits(&self.curr_shape, 0, dy + 1) {
+            dy += 1;
+        }
+        self.curr_shape.cells().map(|(x, y)| (x, y + dy))
+    }
+
+    // Apply a player action. A hard drop locks straight away and a hold brings out a new piece,
+    // so either can end the game
+    pub fn handle_input(&mut self, input: Input) -> UpdateEndState {
+        let grounded = !self.can_move_curr_shape(Dir::Down);
+        let moved = match input {
+            Input::Left => self.shift_curr_shape(Dir::Left),
+            Input::Right => self.shift_curr_shape(Dir::Right),
+            Input::RotateLeft => self.rotate_curr_shape(Dir::Left),
+            Input::RotateRight => self.rotate_curr_shape(Dir::Right),
+            Input::SoftDrop => {
+                self.soft_drop_ticks = SOFT_DROP_HOLD_TICKS;
+                false
+            }, Input::HardDrop => {
+                let mut rows = 0;
+                while self.shift_curr_shape(Dir::Down) {
+                    rows += 1;
                 }
-            }, b's' => {
-                self.curr_shape.pos.1 = floor(self.curr_shape.pos.1 as f64, 0) as f32;
-                while self.can_move_curr_shape(Dir::Down) {
-                    self.curr_shape.pos.1 += 0.5; // Make sure not to skip
+                self.score += rows * HARD_DROP_SCORE;
+                return self.lock_curr_shape();
+            }, Input::Hold => {
+                if self.can_hold {
+                    return self.hold_curr_shape();
                 }
+                false
             }
-            _ => {}
+        };
+
+        // Moving on the ground restarts the lock timer, but only so many times per piece
+        if moved && grounded && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_timer = self.lock_delay_ticks();
+            self.lock_resets += 1;
         }
+        if moved {
+            self.check_lowest_row(); // Kicks can take a piece down
+        }
+        UpdateEndState::Continue
+    }
+
+    // Advance gravity and locking by one tick (1 / TICKS_PER_SEC seconds)
+    pub fn update(&mut self) -> UpdateEndState {
+        self.ticks += 1;
+        let soft_dropping = self.soft_drop_ticks > 0;
+        self.soft_drop_ticks = self.soft_drop_ticks.saturating_sub(1);
 
         if self.can_move_curr_shape(Dir::Down) {
-            self.curr_shape.pos.1 += self.fall_spd * (delta_time_ms as f32 / 1_000.0);
-        } else if self.land_timer > 0.0 { // Allow a few ms for moving b4 settling
-            self.land_timer -= delta_time_ms as f64 / 1_0000.0;
-        } else if self.curr_shape.pos.1 <= 1.0 { // Landed at start means death
+            let mult = if soft_dropping {
+                SOFT_DROP_MULT
+            } else {
+                1
+            };
+            let rows = self.fall(self.gravity * mult);
+            if soft_dropping {
+                self.score += rows * SOFT_DROP_SCORE;
+            }
+            self.check_lowest_row();
+        } else if self.lock_timer > 0 { // Allow some time for moving b4 settling
+            self.lock_timer -= 1;
+        } else {
+            return self.lock_curr_shape();
+        }
+
+        UpdateEndState::Continue
+    }
+
+    // Make the current piece part of the board, clear rows and bring out the next piece
+    fn lock_curr_shape(&mut self) -> UpdateEndState {
+        // Lock out: the whole piece came to rest in the hidden rows
+        if self.curr_shape.cells().iter().all(|(_, y)| *y < 0) {
             return UpdateEndState::Lost;
+        }
+
+        let spin = self.t_spin();
+        self.save_tetromino();
+        let rows = self.check_rows();
+        self.score_lock(rows, spin); // Scored at the level the rows were cleared on
+        self.add_lines(rows as u64);
+
+        self.can_hold = true;
+        self.spawn_next()
+    }
+
+    // Count cleared rows and go up a level (and speed) every lines_per_level of them
+    fn add_lines(&mut self, rows: u64) {
+        self.lines += rows;
+        let level = self.rules.start_level + self.lines / self.rules.lines_per_level;
+        if level != self.level {
+            self.level = level;
+            self.gravity = level_gravity(level);
+        }
+    }
+
+    // Guideline scoring for a lock that cleared some number of rows (maybe none)
+    fn score_lock(&mut self, rows: usize, spin: Spin) {
+        let points = match spin {
+            Spin::None => LINE_SCORES[rows],
+            Spin::Mini => MINI_T_SPIN_SCORES[rows.min(MINI_T_SPIN_SCORES.len() - 1)],
+            Spin::Full => T_SPIN_SCORES[rows]
+        } * self.level;
+        if rows == 0 {
+            self.score += points;
+            self.combo = None;
+            return;
+        }
+
+        // Tetrises and T-spins in a row are worth half again as much
+        let difficult = rows == 4 || spin != Spin::None;
+        self.score += if difficult && self.back_to_back {
+            points * 3 / 2
         } else {
-            self.score += PIECE_SCORE_INC + (self.fall_spd * PIECE_SCORE_FAL_MULT) as u64;
+            points
+        };
+        self.back_to_back = difficult;
 
-            self.save_tetromino();
-            self.check_rows();
+        let combo = self.combo.map_or(0, |combo| combo + 1);
+        self.score += COMBO_SCORE * combo * self.level;
+        self.combo = Some(combo);
+    }
 
-            self.land_timer = LAND_TIME_DELAY_S;
-            self.curr_shape = Tetromino::select();
+    // Three-corner rule: a T that just turned into a spot with 3 of its 4 corners filled.
+    // It's a mini unless both corners it points at are filled or it took the last kick
+    fn t_spin(&self) -> Spin {
+        let kick = match self.last_kick {
+            Some(kick) if self.curr_shape.shape == ShapeType::T => kick,
+            _ => return Spin::None
+        };
+
+        let (x, y) = self.curr_shape.pos;
+        let filled = |(dx, dy): (i16, i16)| self.is_filled(x + dx, y + dy);
+        let corners = [ (-1, -1), (1, -1), (1, 1), (-1, 1) ];
+        if corners.iter().filter(|corner| filled(**corner)).count() < 3 {
+            return Spin::None;
         }
 
+        let front = match self.curr_shape.rotation {
+            Rotation::Spawn => [ (-1, -1), (1, -1) ],
+            Rotation::Right => [ (1, -1), (1, 1) ],
+            Rotation::Two => [ (1, 1), (-1, 1) ],
+            Rotation::Left => [ (-1, 1), (-1, -1) ]
+        };
+        if front.iter().all(|corner| filled(*corner)) || kick == FULL_T_SPIN_KICK {
+            Spin::Full
+        } else {
+            Spin::Mini
+        }
+    }
+
+    // Walls, floor and the ceiling over the buffer count as filled
+    fn is_filled(&self, x: i16, y: i16) -> bool {
+        !self.in_bounds(x, y) || self.blocks[row_index(y)][x as usize] != -1
+    }
+
+    // Inside the walls and between the top of the buffer and the floor
+    fn in_bounds(&self, x: i16, y: i16) -> bool {
+        let top = -(BUFFER_HEIGHT as i16);
+        (0..self.rules.width as i16).contains(&x) && (top..self.rules.height as i16).contains(&y)
+    }
+
+    fn lock_delay_ticks(&self) -> u64 {
+        self.rules.lock_delay_ms * TICKS_PER_SEC / 1_000
+    }
+
+    // A new piece falls from the top with a full lock timer
+    fn reset_piece_state(&mut self) {
+        self.gravity_acc = 0;
+        self.lock_timer = self.lock_delay_ticks();
+        self.lock_resets = 0;
+        self.lowest_row = self.curr_shape.pos.1;
+        self.last_kick = None;
+    }
+
+    // A piece that gets lower than it's been before gets a full lock delay and all its resets back
+    fn check_lowest_row(&mut self) {
+        if self.curr_shape.pos.1 > self.lowest_row {
+            self.lowest_row = self.curr_shape.pos.1;
+            self.lock_timer = self.lock_delay_ticks();
+            self.lock_resets = 0;
+        }
+    }
+
+    // Build up gravity and move the current piece down a cell for every whole unit of it,
+    // stopping on blocks. Returns how many rows it went down
+    fn fall(&mut self, gravity: u32) -> u64 {
+        self.gravity_acc += gravity;
+        let mut rows = 0;
+        while self.gravity_acc >= GRAVITY_UNIT {
+            if !self.can_move_curr_shape(Dir::Down) {
+                self.gravity_acc = 0;
+                break;
+            }
+            self.gravity_acc -= GRAVITY_UNIT;
+            self.shift_curr_shape(Dir::Down);
+            rows += 1;
+        }
+        rows
+    }
+
+    // Put a piece at the spawn point and drop it a row straight away if there's room.
+    // Block out: the game is lost if it spawns on top of settled blocks
+    fn bring_out(&mut self, mut shape: Tetromino) -> UpdateEndState {
+        shape.pos = ((self.rules.width as i16 - 1) / 2, SPAWN_ROW); // Left of center if even
+        self.curr_shape = shape;
+        if !self.fits(&self.curr_shape, 0, 0) {
+            return UpdateEndState::Lost;
+        }
+        if self.can_move_curr_shape(Dir::Down) {
+            self.curr_shape.pos.1 += 1;
+        }
+        self.reset_piece_state();
         UpdateEndState::Continue
     }
 
+    // Take the next piece from the queue and deal a new one onto the back of it
+    fn spawn_next(&mut self) -> UpdateEndState {
+        self.queue.push_back(Tetromino::new(self.randomizer.next(&mut self.rng)));
+        let next = self.queue.pop_front().unwrap();
+        self.bring_out(next)
+    }
+
+    // Put the current piece in hold, back in its spawn state, and bring out the old one
+    fn hold_curr_shape(&mut self) -> UpdateEndState {
+        let held = Tetromino::new(self.curr_shape.shape);
+        self.can_hold = false;
+        match self.hold.replace(held) {
+            Some(prev) => self.bring_out(prev),
+            None => self.spawn_next()
+        }
+    }
+
     // Permanently store the block data of the current shape after landing
     fn save_tetromino(&mut self) {
-        let (shape_x, shape_y) = self.curr_shape.pos;
-        let shape_block_x = floor(shape_x as f64, 0) as i16;
-        let shape_block_y = floor(shape_y as f64, 0) as i16;
-        for coord in self.curr_shape.coords {
-            let (mut coord_x, mut coord_y) = coord;
-            coord_x += shape_block_x;
-            coord_y += shape_block_y;
-
-            self.blocks[coord_y as usize][coord_x as usize] = self.curr_shape.fg as i8;
+        for (coord_x, coord_y) in self.curr_shape.cells() {
+            self.blocks[row_index(coord_y)][coord_x as usize] = self.curr_shape.fg as i8;
         }
     }
 
-    // Check if we can delete rows and shift everything
-    fn check_rows(&mut self) {
+    // Check if we can delete rows and shift everything. Returns how many were cleared
+    fn check_rows(&mut self) -> usize {
         let mut num_filled_rows = 0;
 
-        for y in 0..GRID_HEIGHT {
+        for y in 0..self.blocks.len() {
             let mut row_full = true;
-            for x in 0..GRID_WIDTH {
+            for x in 0..self.rules.width {
                 if self.blocks[y][x] == -1 {
                     row_full = false;
                     break;
@@ -229,130 +546,81 @@ impl GameState {
             if row_full {
                 num_filled_rows += 1;
 
-                // Update game speed
-                self.fall_spd += SPD_INC;
-
-                // Move rows above down (will also replace data in row y, so no clear needed)
-                for y_above in (0..y).rev() {
-                    for x in 0..GRID_WIDTH {
-                        self.blocks[y_above + 1][x] = self.blocks[y_above][x];
-                    }
-                }
+                // Take the row out, which moves the rows above down, and add an empty one on top
+                self.blocks.remove(y);
+                self.blocks.insert(0, vec![ -1; self.rules.width ]);
             }
         }
 
-        if num_filled_rows == 1 {
-            self.score += ROW_SCORE_INC;
-        } else if num_filled_rows > 1 {
-            self.score += ROW_SCORE_INC * ((num_filled_rows - 1) << 1); // eg 100 to 200, 400, 600
-        }
+        num_filled_rows
     }
 
-    fn draw(&mut self, cnv: &mut Canvas, hs_disp: &Vec<&String>) {
-        cnv.draw_strs(&BORDER.to_vec(), (1, 1), BORDER_COLOR, &Reset);
-
-        let score_str = format!("{:020}", self.score);
-        let score_disp = vec![ &score_str ];
-        cnv.draw_strings(&hs_disp, (3, 1), SCORE_COLOR, &Reset);
-        cnv.draw_strings(&score_disp, (3, 2), SCORE_COLOR, &Reset);
-        
-        for y in 0..GRID_HEIGHT {
-            for x in 0..GRID_WIDTH {
-                if self.blocks[y][x] != -1 {
-                    cnv.draw_strs(
-                        &vec![ SHAPE_STR ],
-                        ((x * SHAPE_WIDTH + 2) as u16, (y + SHAPE_DRAW_OFFSET as usize) as u16),
-                        SHAPE_COLORS[self.blocks[y][x] as usize], &Reset
-                    );
-                }
-            }
+    // Move the current piece one cell if nothing is in the way
+    fn shift_curr_shape(&mut self, dir: Dir) -> bool {
+        if !self.can_move_curr_shape(dir) {
+            return false;
         }
-
-        // Dealing with whole display! Not just grid
-        let (shape_x, shape_y) = self.curr_shape.pos;
-        let shape_block_x = floor(shape_x as f64, 0) as i16;
-        let shape_block_y = floor(shape_y as f64, 0) as i16;
-        for coord in self.curr_shape.coords {
-            let (mut coord_x, mut coord_y) = coord;
-            coord_x += shape_block_x;
-            coord_y += shape_block_y;
-
-            let x = coord_x * SHAPE_WIDTH as i16 + 2;
-            let y = coord_y + SHAPE_DRAW_OFFSET;
-
-            cnv.draw_strs(
-                &vec![ SHAPE_STR ], (x as u16, y as u16), SHAPE_COLORS[self.curr_shape.fg], &Reset
-            );
+        match dir {
+            Dir::Left => self.curr_shape.pos.0 -= 1,
+            Dir::Right => self.curr_shape.pos.0 += 1,
+            Dir::Down => self.curr_shape.pos.1 += 1
         }
-
-        cnv.flush();
+        self.last_kick = None; // Moving after a turn means it's not a spin
+        true
     }
 
     // Check if a block is able to move in a given direction
-    fn can_move_curr_shape(&mut self, dir: Dir) -> bool {
-        // Get position in a grid format (we want slow movement, so we use actually use floats)
-        let (shape_x, shape_y) = self.curr_shape.pos;
-        let shape_block_x = floor(shape_x as f64, 0) as i16;
-        let shape_block_y = floor(shape_y as f64, 0) as i16;
-
-        for coord in self.curr_shape.coords {
-            let (mut coord_x, mut coord_y) = coord;
-            coord_x += shape_block_x;
-            coord_y += shape_block_y;
-
-            // Adjust the x/y in the direction we want to test
-            match dir {
-                Dir::Left => {
-                    coord_x -= 1;
-                }, Dir::Right => {
-                    coord_x += 1;
-                }, Dir::Down => {
-                    coord_y += 1;
-                }
-            }
-
-            // Deal with just grid! Not whole display
-            if coord_x < 0 || coord_x >= GRID_WIDTH as i16 || coord_y >= GRID_HEIGHT as i16 {
-                return false;
-            }
-
-            if coord_y >= 0 && self.blocks[coord_y as usize][coord_x as usize] != -1 {
-                return false;
-            }
-        }
-        true
+    fn can_move_curr_shape(&self, dir: Dir) -> bool {
+        let (dx, dy) = match dir {
+            Dir::Left => (-1, 0),
+            Dir::Right => (1, 0),
+            Dir::Down => (0, 1)
+        };
+        self.fits(&self.curr_shape, dx, dy)
     }
 
-    // Basically check if "can_move" to the current position after rotation
-    fn can_rotate_curr_shape(&mut self, dir: Dir) -> bool {
+    // Turn using SRS: try each wall kick in order and take the first that fits
+    fn rotate_curr_shape(&mut self, dir: Dir) -> bool {
         if dir == Dir::Down {
-            return true;
+            return false;
         }
 
         // Create temp shape and rotate it
-        let mut temp_shape = self.curr_shape.clone();
+        let mut temp_shape = self.curr_shape;
         temp_shape.rotate(dir);
+        for (i, &(kick_x, kick_y)) in self.curr_shape.kicks(dir).iter().enumerate() {
+            if self.fits(&temp_shape, kick_x, kick_y) {
+                temp_shape.pos.0 += kick_x;
+                temp_shape.pos.1 += kick_y;
+                self.curr_shape = temp_shape;
+                self.last_kick = Some(i);
+                return true;
+            }
+        }
+        false
+    }
 
-        // Check if it's valid
-        let (shape_x, shape_y) = temp_shape.pos;
-        let shape_block_x = floor(shape_x as f64, 0) as i16;
-        let shape_block_y = floor(shape_y as f64, 0) as i16;
-        for coord in temp_shape.coords {
-            let (mut coord_x, mut coord_y) = coord;
-            coord_x += shape_block_x;
-            coord_y += shape_block_y;
-
-            // NOTE: Unlike can_move, we don't want to adjust the coords
+    // Check a shape offset by (dx, dy) is inside the grid and not on top of other blocks
+    fn fits(&self, shape: &Tetromino, dx: i16, dy: i16) -> bool {
+        for (mut coord_x, mut coord_y) in shape.cells() {
+            coord_x += dx;
+            coord_y += dy;
 
             // Deal with just grid! Not whole display
-            if coord_x < 0 || coord_x >= GRID_WIDTH as i16 || coord_y >= GRID_HEIGHT as i16 {
-                return false;
-            }
-
-            if coord_y >= 0 && self.blocks[coord_y as usize][coord_x as usize] != -1 {
+            if self.is_filled(coord_x, coord_y) {
                 return false;
             }
         }
         true
     }
 }
+
+// Index into the board for a row of the grid, which may be a buffer row (y < 0)
+fn row_index(y: i16) -> usize {
+    (y + BUFFER_HEIGHT as i16) as usize
+}
+
+// Gravity in GRAVITY_UNITs per tick for a level
+fn level_gravity(level: u64) -> u32 {
+    LEVEL_GRAVITY[(level.max(1) as usize - 1).min(LEVEL_GRAVITY.len() - 1)]
+}
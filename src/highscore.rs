@@ -7,34 +7,199 @@ use serde::{
     Serialize, Deserialize
 };
 use confy::{
-    load, store
+    load, store, ConfyError
 };
 use rand::{
     Rng, thread_rng
 };
+use hmac::{
+    Hmac, Mac
+};
+use sha2::Sha256;
 use std::{
     hash::{
         Hash, Hasher
-    }, collections::hash_map::DefaultHasher
+    }, collections::hash_map::DefaultHasher,
+    time::{
+        SystemTime, UNIX_EPOCH
+    }
+};
+use crate::{
+    config::{
+        Display, KeyBindings
+    }, game::{
+        GameState, Ruleset, TICKS_PER_SEC
+    }
 };
 
-const APP_NAME: &'static str = "tetris-cli";
+const APP_NAME: &str = "tetris-cli";
+
+/*
+ * How the table is hashed, saved with it so the scheme can change without losing scores:
+ * 1 - std's DefaultHasher over the salt and table. Its output can change between Rust releases
+ * 2 - HMAC-SHA256 keyed with HASH_KEY over the salt and table, written as lowercase hex
+ * Saves with no version are 1. Older versions are rehashed with the newest when loaded
+ */
+const DEFAULT_HASHER_VERSION: u32 = 1;
+const HMAC_SHA256_VERSION: u32 = 2;
+pub const HASH_VERSION: u32 = HMAC_SHA256_VERSION;
+const HASH_KEY: &[u8] = b"tetris-cli high scores";
+
+pub const MAX_HIGH_SCORES: usize = 10;
+pub const MAX_NAME_LEN: usize = 10;
+pub const DEFAULT_NAME: &str = "Player"; // For blank names and high scores from before the table
+
+// One game in the high score table
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u64,
+    pub lines: u64,
+    pub level: u64,
+    pub duration_secs: u64, // Time spent playing, not counting pauses
+    pub date: String, // Day the game ended as YYYY-MM-DD (UTC). Empty if unknown
+    pub mode: String // Randomizer and well size, e.g. "bag7 10x20"
+}
+
+impl HighScore {
+    // An entry for a finished game, dated today
+    pub fn new(name: &str, state: &GameState) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        HighScore::ended_at(name, state, now.as_secs())
+    }
 
-// High score saving
+    // An entry for a game that ended unix_secs seconds into 1970 (UTC)
+    pub fn ended_at(name: &str, state: &GameState, unix_secs: u64) -> Self {
+        let rules = state.rules();
+        Self {
+            name: name.chars().take(MAX_NAME_LEN).collect(),
+            score: state.score(),
+            lines: state.lines(),
+            level: state.level(),
+            duration_secs: state.ticks() / TICKS_PER_SEC,
+            date: date(unix_secs),
+            mode: format!("{} {}x{}", rules.randomizer, rules.width, rules.height)
+        }
+    }
+
+    // Every field in a fixed order, for hashing
+    fn to_text(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}\n",
+            self.name, self.score, self.lines, self.level, self.duration_secs, self.date, self.mode
+        )
+    }
+}
+
+// Turn seconds since 1970 into a YYYY-MM-DD date, using Howard Hinnant's civil_from_days
+fn date(unix_secs: u64) -> String {
+    let days = unix_secs / (24 * 60 * 60) + 719_468; // Count from 0000-03-01 instead
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524
+        - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // 0 is March
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// High score saving, plus the player's settings which live in the same config file
 #[derive(Serialize, Deserialize)]
 pub struct SaveData {
-    high_score: u64,
-    high_score_salt: String, // Must match or HS resets to 0
-    high_score_hash: String
+    #[serde(default = "old_hash_version")]
+    hash_version: u32,
+    #[serde(default)]
+    high_scores_salt: String, // Must match or the table is thrown out
+    #[serde(default)]
+    high_scores_hash: String,
+    #[serde(default)]
+    high_scores: Vec<HighScore>, // Best first
+
+    // Single high score from older saves. Read to start the table, never written back
+    #[serde(default, skip_serializing)]
+    high_score: Option<u64>,
+    #[serde(default, skip_serializing)]
+    high_score_salt: String,
+    #[serde(default, skip_serializing)]
+    high_score_hash: String,
+
+    #[serde(default)]
+    pub rules: Ruleset,
+    #[serde(default)]
+    pub display: Display,
+    #[serde(default)]
+    pub keys: KeyBindings,
+
+    #[serde(skip)]
+    read_only: bool // The table is from a newer version. Don't write over it
 }
 
 impl SaveData {
-    pub fn load_config() -> Self {
-        match load(APP_NAME) {
-            Err(_) => {
-                SaveData::default()
-            }, Ok(save) => save
+    // Errors if the file can't be read or has something in it that can't be understood, like a
+    // misspelled key name, rather than quietly playing with (and later saving) the defaults.
+    // A missing file is created with the defaults
+    pub fn load_config() -> Result<Self, String> {
+        let mut save: Self = load(APP_NAME).map_err(|err| match &err {
+            ConfyError::BadTomlData(cause) => cause.to_string(),
+            // confy's messages for these don't say what went wrong
+            ConfyError::GeneralLoadError(cause) | ConfyError::OpenConfigurationFileError(cause)
+                    | ConfyError::ReadConfigurationFileError(cause)
+                    | ConfyError::WriteConfigurationFileError(cause) => {
+                format!("{} {}", err, cause)
+            }, _ => err.to_string()
+        })?;
+        // A newer version's table can't be checked here, so keep it as it is on disk
+        save.read_only = save.table_hash().is_none();
+        if save.migrate() {
+            save.store_config();
         }
+        Ok(save)
+    }
+
+    // Write the table and settings back to the config file
+    pub fn store_config(&self) {
+        if !self.read_only {
+            store(APP_NAME, self).unwrap();
+        }
+    }
+
+    // Bring a save from an older version up to date: rehash the table with the newest scheme
+    // and move a high score from before the table into it. Done by load_config, for saves read
+    // some other way. Returns true if anything changed, so it should be written back.
+    // Saves from newer versions are left alone
+    pub fn migrate(&mut self) -> bool {
+        if self.table_hash().is_none() {
+            return false;
+        }
+
+        let mut changed = false;
+        if self.hash_version == DEFAULT_HASHER_VERSION {
+            let table = self.high_scores().to_vec();
+            self.hash_version = HASH_VERSION;
+            self.set_table(table);
+            changed = true;
+        }
+
+        if let Some(old_score) = self.high_score.take() {
+            let salt_and_hs = self.high_score_salt.clone() + &old_score.to_string();
+            if old_score > 0 && default_hash(&salt_and_hs) == self.high_score_hash
+                    && self.high_scores().is_empty() {
+                self.insert(HighScore {
+                    name: String::from(DEFAULT_NAME),
+                    score: old_score,
+                    lines: 0,
+                    level: 0,
+                    duration_secs: 0,
+                    date: String::new(),
+                    mode: String::new()
+                });
+            }
+            changed = true;
+        }
+        changed
     }
 
     fn get_random_hash() -> String {
@@ -48,46 +213,95 @@ impl SaveData {
         ret
     }
 
-    fn get_hash(msg: &String) -> String {
-        let mut hasher = DefaultHasher::new();
-        msg.hash(&mut hasher);
-        let num = hasher.finish();
-        format!("{:x}", num)
+    // Hash of the salt and table with the save's scheme. None if the scheme isn't known
+    fn table_hash(&self) -> Option<String> {
+        let table: String = self.high_scores.iter().map(HighScore::to_text).collect();
+        let msg = self.high_scores_salt.clone() + &table;
+        match self.hash_version {
+            DEFAULT_HASHER_VERSION => Some(default_hash(&msg)),
+            HMAC_SHA256_VERSION => Some(hmac_sha256(&msg)),
+            _ => None
+        }
     }
 
-    pub fn from_value(high_score: u64) -> Self {
-        let high_score_str = format!("{}", high_score);
-        let salt = SaveData::get_random_hash();
-        let salt_and_hs = salt.clone() + &high_score_str;
-        let hash = SaveData::get_hash(&salt_and_hs);
+    // Replace the table, with a fresh salt and hash
+    fn set_table(&mut self, table: Vec<HighScore>) {
+        self.high_scores = table;
+        self.high_scores_salt = SaveData::get_random_hash();
+        self.high_scores_hash = self.table_hash().unwrap_or_default();
+    }
 
-        return Self {
-            high_score,
-            high_score_salt: salt,
-            high_score_hash: hash
+    // The table, best first. Empty if it was edited by hand
+    pub fn high_scores(&self) -> &[HighScore] {
+        if self.table_hash().as_ref() == Some(&self.high_scores_hash) {
+            &self.high_scores
+        } else {
+            &[]
         }
     }
 
-    pub fn save_value(high_score: u64) {
-        let save = SaveData::from_value(high_score);
-        store(APP_NAME, save).unwrap();
+    pub fn best_score(&self) -> u64 {
+        self.high_scores().first().map_or(0, |entry| entry.score)
     }
 
-    pub fn assert_hs(&self) -> u64 {
-        let high_score_str = format!("{}", self.high_score);
-        let salt_and_hs = self.high_score_salt.clone() + &high_score_str;
-        let test_hash = SaveData::get_hash(&salt_and_hs);
-
-        if self.high_score_hash != test_hash {
-            0
+    // Where a score would go in the table, if it's good enough to get in. Never for a table
+    // hashed by a newer version, since it can't be added to without losing it
+    pub fn rank(&self, score: u64) -> Option<usize> {
+        self.table_hash()?;
+        let rank = self.high_scores().iter().take_while(|entry| entry.score >= score).count();
+        if score > 0 && rank < MAX_HIGH_SCORES {
+            Some(rank)
         } else {
-            self.high_score
+            None
         }
     }
+
+    // Put an entry in the table below any equal scores, dropping whatever falls off the end.
+    // Returns where it went, or None if it didn't make it. Doesn't save to the config file
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let rank = self.rank(entry.score)?;
+        let mut table = self.high_scores().to_vec();
+        table.insert(rank, entry);
+        table.truncate(MAX_HIGH_SCORES);
+        self.set_table(table);
+        Some(rank)
+    }
 }
 
 impl Default for SaveData {
     fn default() -> Self {
-        SaveData::from_value(0)
+        let mut save = Self {
+            hash_version: HASH_VERSION,
+            high_scores_salt: String::new(),
+            high_scores_hash: String::new(),
+            high_scores: Vec::new(),
+            high_score: None,
+            high_score_salt: String::new(),
+            high_score_hash: String::new(),
+            rules: Ruleset::default(),
+            display: Display::default(),
+            keys: KeyBindings::default(),
+            read_only: false
+        };
+        save.set_table(Vec::new());
+        save
     }
 }
+
+// Saves from before the version field used the first scheme
+fn old_hash_version() -> u32 {
+    DEFAULT_HASHER_VERSION
+}
+
+fn default_hash(msg: &String) -> String {
+    let mut hasher = DefaultHasher::new();
+    msg.hash(&mut hasher);
+    let num = hasher.finish();
+    format!("{:x}", num)
+}
+
+fn hmac_sha256(msg: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(HASH_KEY).expect("HMAC takes keys of any size");
+    mac.update(msg.as_bytes());
+    mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,97 @@
+/*
+ * Author: Dylan Turner
+ * Description: Where key presses come from, so a game can be played by something besides a keyboard
+ */
+
+use std::collections::VecDeque;
+use crate::config::Key;
+
+// A source of key presses. Front ends ask for a key once per tick, so a key's tick is
+// how many times it was asked before that key came out
+pub trait KeySource {
+    fn get_key(&mut self) -> Option<Key>;
+}
+
+// Keys set ahead of time to come out on given ticks. Keys due on the same tick come out
+// one per tick after it, in order
+pub struct Scripted {
+    events: VecDeque<(u64, Key)>,
+    tick: u64
+}
+
+impl Scripted {
+    pub fn new(mut events: Vec<(u64, Key)>) -> Self {
+        events.sort_by_key(|(tick, _)| *tick); // Stable, so same tick keys stay in order
+        Self {
+            events: events.into(),
+            tick: 0
+        }
+    }
+
+    // Read events written by Recorder::to_text, one "<tick> <key name>" per line
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+        for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let (tick, key) = line.trim().split_once(' ')
+                .ok_or_else(|| format!("Line {}: expected '<tick> <key>'", i + 1))?;
+            let tick = tick.parse()
+                .map_err(|_| format!("Line {}: invalid tick '{}'", i + 1, tick))?;
+            let key = key.parse().map_err(|msg| format!("Line {}: {}", i + 1, msg))?;
+            events.push((tick, key));
+        }
+        Ok(Scripted::new(events))
+    }
+
+    // Nothing left to come out
+    pub fn is_done(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl KeySource for Scripted {
+    fn get_key(&mut self) -> Option<Key> {
+        let key = match self.events.front() {
+            Some((tick, _)) if *tick <= self.tick => self.events.pop_front().map(|(_, key)| key),
+            _ => None
+        };
+        self.tick += 1;
+        key
+    }
+}
+
+// Passes keys through from another source, writing down which tick each came on
+pub struct Recorder<S: KeySource> {
+    source: S,
+    events: Vec<(u64, Key)>,
+    tick: u64
+}
+
+impl<S: KeySource> Recorder<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            events: Vec::new(),
+            tick: 0
+        }
+    }
+
+    pub fn events(&self) -> &[(u64, Key)] {
+        &self.events
+    }
+
+    // One "<tick> <key name>" per line, for Scripted::parse to play back
+    pub fn to_text(&self) -> String {
+        self.events.iter().map(|(tick, key)| format!("{} {}\n", tick, key.name())).collect()
+    }
+}
+
+impl<S: KeySource> KeySource for Recorder<S> {
+    fn get_key(&mut self) -> Option<Key> {
+        let key = self.source.get_key();
+        if let Some(key) = key {
+            self.events.push((self.tick, key));
+        }
+        self.tick += 1;
+        key
+    }
+}
@@ -7,89 +7,433 @@ use termion::{
     clear::All, cursor::{ Goto, Hide, Show },
     raw::{ RawTerminal, IntoRawMode },
     color::{ Color, Fg, Bg, Reset },
-    async_stdin, AsyncReader
+    event, input::{ Keys, TermRead },
+    async_stdin, terminal_size, AsyncReader
+};
+use std::io::{ Write, stdout, Stdout };
+use tetris_cli::{
+    config::{
+        Anchor, Key
+    }, input::KeySource
 };
-use std::io::{ Write, stdout, Stdout, Read };
 
-// Double block shapes in a 10x20 grid plus borders and extra enter space
-pub const SHAPE_STR: &'static str = "██";
+// Double block shapes so cells come out about square
+pub const SHAPE_STR: &str = "██";
 pub const SHAPE_WIDTH: usize = 2;
-pub const GRID_WIDTH: usize = 10;
-pub const GRID_HEIGHT: usize = 20;
-pub const DISP_WIDTH: u16 = (SHAPE_WIDTH * GRID_WIDTH) as u16 + 2; 
-pub const DISP_HEIGHT: u16 = GRID_HEIGHT as u16 + 5;
 
-// An object that lets you draw to it
-pub struct Canvas {
-    out: RawTerminal<Stdout>
+// One character on screen and its colors, as indices into the frame's palette
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    glyph: char,
+    fg: usize,
+    bg: usize
 }
 
-impl Canvas {
-    pub fn new() -> Self {
-        let mut out = stdout().into_raw_mode().unwrap();
-        write!(out, "{}{}", All, Goto(1, 1)).unwrap();
-        out.flush().unwrap();
+const BLANK: Cell = Cell { glyph: ' ', fg: 0, bg: 1 }; // Palette starts with the reset colors
+const MAX_REWRITE: usize = 4; // Unchanged cells worth writing again to skip a cursor move
 
-        write!(out, "{}", Hide).unwrap(); // Hide the cursor
+// What's been drawn but not shown yet, and where on the screen drawing goes
+pub struct Frame {
+    size: (u16, u16), // Screen size when last checked
+    content_width: u16, // Width of what's being drawn, for placing it
+    anchor: Anchor,
+    origin_x: u16, // Added to the column of everything drawn
+    cells: Vec<Vec<Cell>>,
+    palette: Vec<String> // Escape codes for every color used so far
+}
 
-        Self { out }
+impl Frame {
+    fn new(size: (u16, u16)) -> Self {
+        Self {
+            size,
+            content_width: 0,
+            anchor: Anchor::Left,
+            origin_x: 0,
+            cells: blank_cells(size),
+            palette: vec![ Fg(Reset).to_string(), Bg(Reset).to_string() ]
+        }
     }
 
-    pub fn reset(&mut self) {
-        write!(self.out, "{}\r\n", Show).unwrap();
-        self.out.flush().unwrap();
+    // Start over from a blank screen of a new size
+    fn resize(&mut self, size: (u16, u16)) {
+        self.size = size;
+        self.cells = blank_cells(size);
+        self.update_origin();
+    }
+
+    fn update_origin(&mut self) {
+        let origin_x = self.anchor.offset(self.content_width, self.size.0);
+        if origin_x != self.origin_x {
+            self.origin_x = origin_x;
+            self.clear(); // Don't leave the old frame behind
+        }
+    }
+
+    fn clear(&mut self) {
+        for row in &mut self.cells {
+            row.fill(BLANK);
+        }
+    }
+
+    // Write one line of text, dropping anything off screen
+    fn put(&mut self, line: &str, (x, y): (u16, u16), fg: &dyn Color, bg: &dyn Color) {
+        let fg = self.color_index(Fg(fg).to_string());
+        let bg = self.color_index(Bg(bg).to_string());
+        let row = match y.checked_sub(1).and_then(|y| self.cells.get_mut(y as usize)) {
+            Some(row) => row,
+            None => return
+        };
+        let start = (x + self.origin_x).saturating_sub(1) as usize;
+        for (cell, glyph) in row.iter_mut().skip(start).zip(line.chars()) {
+            *cell = Cell { glyph, fg, bg };
+        }
+    }
+
+    fn color_index(&mut self, code: String) -> usize {
+        match self.palette.iter().position(|known| *known == code) {
+            Some(i) => i,
+            None => {
+                self.palette.push(code);
+                self.palette.len() - 1
+            }
+        }
+    }
+}
+
+fn blank_cells((width, height): (u16, u16)) -> Vec<Vec<Cell>> {
+    vec![ vec![ BLANK; width as usize ]; height as usize ]
+}
+
+// Something the game can be drawn on. Positions are 1 based like the terminal's cursor
+pub trait Draw {
+    fn frame(&self) -> &Frame;
+    fn frame_mut(&mut self) -> &mut Frame;
+
+    // Check if the screen changed size. If it did, it's cleared for a fresh frame
+    fn resized(&mut self) -> bool;
+
+    // Show everything drawn since the last flush
+    fn flush(&mut self);
+
+    // Line up the next frames of something width wide with one side or the middle of the
+    // screen. Drawing positions stay relative to its top left corner
+    fn place(&mut self, width: u16, anchor: Anchor) {
+        let frame = self.frame_mut();
+        frame.content_width = width;
+        frame.anchor = anchor;
+        frame.update_origin();
+    }
+
+    fn fits(&self, (width, height): (u16, u16)) -> bool {
+        let size = self.frame().size;
+        size.0 >= width && size.1 >= height
+    }
+
+    // Blank the whole screen on the next flush
+    fn clear(&mut self) {
+        self.frame_mut().clear();
     }
 
     // Can't figure out how to combine the two due to &'static part of str
-    pub fn draw_strs(
+    fn draw_strs(
             &mut self, lines: &Vec<&'static str>, pos: (u16, u16),
             fg: &dyn Color, bg: &dyn Color) {
         let (x, mut y) = pos;
         for line in lines {
-            write!(
-                self.out, "{}{}{}{}{}{}",
-                Goto(x, y), Fg(fg), Bg(bg), line,
-                Fg(Reset), Bg(Reset)
-            ).unwrap();
+            self.frame_mut().put(line, (x, y), fg, bg);
             y += 1;
         }
     }
 
-    pub fn draw_strings(
+    fn draw_strings(
             &mut self, lines: &Vec<&String>, pos: (u16, u16),
             fg: &dyn Color, bg: &dyn Color) {
         let (x, mut y) = pos;
         for line in lines {
-            write!(
-                self.out, "{}{}{}{}{}{}",
-                Goto(x, y), Fg(fg), Bg(bg), line,
-                Fg(Reset), Bg(Reset)
-            ).unwrap();
+            self.frame_mut().put(line, (x, y), fg, bg);
             y += 1;
         }
     }
+}
+
+/*
+ * Draws to the terminal. Flush only sends the cells that differ from what's already on screen
+ * (the front buffer), with as few cursor moves and color changes as it can get away with
+ */
+pub struct Canvas {
+    out: RawTerminal<Stdout>,
+    front: Vec<Vec<Cell>>,
+    back: Frame
+}
+
+impl Canvas {
+    pub fn new() -> Self {
+        let mut out = stdout().into_raw_mode().unwrap();
+        write!(out, "{}{}", All, Goto(1, 1)).unwrap();
+        out.flush().unwrap();
+
+        write!(out, "{}", Hide).unwrap(); // Hide the cursor
+
+        let size = terminal_size().unwrap();
+        Self {
+            out,
+            front: blank_cells(size),
+            back: Frame::new(size)
+        }
+    }
+
+    pub fn reset(&mut self) {
+        write!(self.out, "{}{}{}\r\n", Fg(Reset), Bg(Reset), Show).unwrap();
+        self.out.flush().unwrap();
+    }
+}
+
+impl Draw for Canvas {
+    fn frame(&self) -> &Frame {
+        &self.back
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.back
+    }
+
+    fn resized(&mut self) -> bool {
+        let size = terminal_size().unwrap_or(self.back.size);
+        if size == self.back.size {
+            return false;
+        }
+
+        // The terminal may have moved what was on screen around, so start again from blank
+        write!(self.out, "{}", All).unwrap();
+        self.front = blank_cells(size);
+        self.back.resize(size);
+        true
+    }
 
-    pub fn flush(&mut self) {
+    fn flush(&mut self) {
+        let out = changes(&self.front, &self.back);
+        if !out.is_empty() {
+            write!(self.out, "{}{}{}", out, Fg(Reset), Bg(Reset)).unwrap();
+            self.front.clone_from(&self.back.cells);
+        }
         self.out.flush().unwrap();
     }
 }
 
-// An object that lets you read key presses
+// Draws to memory instead of a terminal, so tests can see what would be on screen
+#[cfg(test)]
+pub struct Headless {
+    shown: Vec<Vec<Cell>>, // As of the last flush
+    back: Frame,
+    resize_pending: bool
+}
+
+#[cfg(test)]
+impl Headless {
+    pub fn new(size: (u16, u16)) -> Self {
+        Self {
+            shown: blank_cells(size),
+            back: Frame::new(size),
+            resize_pending: false
+        }
+    }
+
+    // Act like the terminal was resized. Takes effect at the next check for it
+    pub fn resize(&mut self, size: (u16, u16)) {
+        self.back.size = size;
+        self.resize_pending = true;
+    }
+
+    // Text on screen, one string per row with trailing spaces trimmed
+    pub fn lines(&self) -> Vec<String> {
+        self.shown.iter().map(|row| {
+            let line: String = row.iter().map(|cell| cell.glyph).collect();
+            String::from(line.trim_end())
+        }).collect()
+    }
+
+    // Escape code for the text color of the cell at a position (1 based)
+    pub fn fg(&self, (x, y): (u16, u16)) -> &str {
+        &self.back.palette[self.shown[y as usize - 1][x as usize - 1].fg]
+    }
+}
+
+#[cfg(test)]
+impl Draw for Headless {
+    fn frame(&self) -> &Frame {
+        &self.back
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.back
+    }
+
+    fn resized(&mut self) -> bool {
+        if !self.resize_pending {
+            return false;
+        }
+        self.resize_pending = false;
+        self.shown = blank_cells(self.back.size);
+        self.back.resize(self.back.size);
+        true
+    }
+
+    fn flush(&mut self) {
+        self.shown.clone_from(&self.back.cells);
+    }
+}
+
+// What to send to turn a screen showing front into the frame: only the cells that differ, with as
+// few cursor moves and color changes as it can get away with
+fn changes(front: &[Vec<Cell>], back: &Frame) -> String {
+    let mut out = String::new();
+    let mut cursor = None; // Where the terminal will write next, if we know
+    let mut pen = None; // Colors the terminal is writing with, if we know
+    for (y, (back_row, front_row)) in back.cells.iter().zip(front).enumerate() {
+        for (x, (cell, old)) in back_row.iter().zip(front_row).enumerate() {
+            if cell == old {
+                continue;
+            }
+
+            match cursor {
+                Some((cursor_x, cursor_y)) if cursor_y == y && cursor_x <= x
+                        && x - cursor_x <= MAX_REWRITE => {
+                    // Writing over a few unchanged cells is shorter than a cursor move
+                    for same in &back_row[cursor_x..x] {
+                        emit(&mut out, &mut pen, &back.palette, *same);
+                    }
+                }, _ => out += &Goto(x as u16 + 1, y as u16 + 1).to_string()
+            }
+            emit(&mut out, &mut pen, &back.palette, *cell);
+
+            cursor = if x + 1 < back_row.len() {
+                Some((x + 1, y))
+            } else {
+                None // Some terminals wrap straight away at the edge, some don't
+            };
+        }
+    }
+    out
+}
+
+// Add a cell to the output, changing colors first if the pen doesn't match
+fn emit(out: &mut String, pen: &mut Option<(usize, usize)>, palette: &[String], cell: Cell) {
+    let (fg, bg) = pen.unwrap_or((usize::MAX, usize::MAX));
+    if cell.fg != fg {
+        *out += &palette[cell.fg];
+    }
+    if cell.bg != bg {
+        *out += &palette[cell.bg];
+    }
+    out.push(cell.glyph);
+    *pen = Some((cell.fg, cell.bg));
+}
+
+// An object that lets you read key presses, turning escape sequences into single keys
 pub struct KeyReader {
-    inp: AsyncReader
+    inp: Keys<AsyncReader>
 }
 
 impl KeyReader {
     pub fn new() -> Self {
-        let inp = async_stdin();
+        let inp = async_stdin().keys();
         Self {
             inp
         }
     }
 
-    pub fn get_key(&mut self) -> u8 {
-        let mut key_bytes: [u8; 1] = [ 0 ];
-        self.inp.read(&mut key_bytes).unwrap();
-        return  key_bytes[0];
+}
+
+impl KeySource for KeyReader {
+    // Next key pressed, if any. Keys we have no use for are skipped
+    fn get_key(&mut self) -> Option<Key> {
+        loop {
+            let key = match self.inp.next()? {
+                Ok(event::Key::Char('\n')) => Key::Enter,
+                Ok(event::Key::Char('\t')) => Key::Tab,
+                Ok(event::Key::Char(c)) => Key::Char(c),
+                Ok(event::Key::Ctrl(c)) => Key::Ctrl(c),
+                Ok(event::Key::Alt(c)) => Key::Alt(c),
+                Ok(event::Key::F(n)) => Key::F(n),
+                Ok(event::Key::Up) => Key::Up,
+                Ok(event::Key::Down) => Key::Down,
+                Ok(event::Key::Left) => Key::Left,
+                Ok(event::Key::Right) => Key::Right,
+                Ok(event::Key::Home) => Key::Home,
+                Ok(event::Key::End) => Key::End,
+                Ok(event::Key::PageUp) => Key::PageUp,
+                Ok(event::Key::PageDown) => Key::PageDown,
+                Ok(event::Key::Insert) => Key::Insert,
+                Ok(event::Key::Delete) => Key::Delete,
+                Ok(event::Key::Backspace) => Key::Backspace,
+                Ok(event::Key::BackTab) => Key::BackTab,
+                Ok(event::Key::Esc) => Key::Esc,
+                _ => continue
+            };
+            return Some(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use termion::color::Red;
+    use super::*;
+
+    // A frame with some lines of text drawn on it, red on the default background
+    fn frame_with(size: (u16, u16), lines: &[(&str, (u16, u16))]) -> Frame {
+        let mut frame = Frame::new(size);
+        for (line, pos) in lines {
+            frame.put(line, *pos, &Red, &Reset);
+        }
+        frame
+    }
+
+    fn gotos(out: &str) -> usize {
+        out.matches('H').count() // Every cursor move ends in an H, and the tests don't draw any
+    }
+
+    #[test]
+    fn sends_nothing_for_a_frame_already_on_screen() {
+        let frame = frame_with((10, 3), &[ ("abc", (2, 2)) ]);
+        let front = blank_cells(frame.size);
+        assert!(!changes(&front, &frame).is_empty());
+        assert_eq!(changes(&frame.cells, &frame), "");
+    }
+
+    #[test]
+    fn writes_over_a_few_unchanged_cells_instead_of_moving() {
+        let front = blank_cells((20, 1));
+
+        // The blanks between are sent again, back in the default text color
+        let frame = frame_with((20, 1), &[ ("a", (1, 1)), ("b", (6, 1)) ]);
+        let out = changes(&front, &frame);
+        assert_eq!(gotos(&out), 1);
+        assert!(out.starts_with(&Goto(1, 1).to_string()));
+        assert!(out.ends_with(&format!("a{}    {}b", Fg(Reset), Fg(Red))));
+
+        // Any further apart and a cursor move is shorter
+        let frame = frame_with((20, 1), &[ ("a", (1, 1)), ("b", (7, 1)) ]);
+        let out = changes(&front, &frame);
+        assert_eq!(gotos(&out), 2);
+        assert!(out.contains(&format!("{}b", Goto(7, 1))));
+    }
+
+    #[test]
+    fn only_sends_colors_when_they_change() {
+        let frame = frame_with((10, 2), &[ ("abc", (1, 1)), ("def", (1, 2)) ]);
+        let out = changes(&blank_cells(frame.size), &frame);
+        assert_eq!(out.matches(&Fg(Red).to_string()).count(), 1);
+        assert_eq!(out.matches(&Bg(Reset).to_string()).count(), 1);
+        assert!(out.contains(&format!("{}def", Goto(1, 2))));
+    }
+
+    #[test]
+    fn moves_the_cursor_again_after_the_right_edge() {
+        // The terminal may or may not have wrapped after the last column, so don't count on it
+        let frame = frame_with((4, 2), &[ ("abcd", (1, 1)), ("e", (1, 2)) ]);
+        let out = changes(&blank_cells(frame.size), &frame);
+        assert_eq!(gotos(&out), 2);
+        assert!(out.ends_with(&format!("d{}e", Goto(1, 2))));
     }
 }
@@ -0,0 +1,13 @@
+/*
+ * Author: Dylan Turner
+ * Description: Terminal-free tetris engine shared by the cli and other front ends
+ */
+
+pub mod clock;
+pub mod config;
+pub mod game;
+pub mod highscore;
+pub mod input;
+pub mod randomizer;
+pub mod replay;
+pub mod tetromino;
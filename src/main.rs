@@ -4,30 +4,41 @@
  */
 
 mod io;
-mod game;
-mod highscore;
-mod tetromino;
+mod scores;
+mod tui;
 
 use std::{
-    thread::sleep,
-    time::{
-        Instant, Duration
-    }
+    env::args,
+    thread::sleep
 };
 use termion::{
     color::{
         Color, White, Reset
     }, terminal_size
 };
+use tetris_cli::{
+    clock::Clock,
+    config::{
+        Action, KeyBindings
+    }, game::{
+        GameState, MAX_START_LEVEL
+    }, input::KeySource, highscore::{
+        HighScore, SaveData
+    }, randomizer::RandomizerKind, replay::{
+        Playback, Replay
+    }
+};
 use crate::io::{
-    DISP_WIDTH, DISP_HEIGHT, Canvas, KeyReader
+    Canvas, Draw, KeyReader
+};
+use crate::scores::{
+    enter_name, show_high_scores, TABLE_HEIGHT, TABLE_WIDTH
 };
-use crate::game::{
-    FPS, GameState
+use crate::tui::{
+    controls_help, play, wait_for_room, watch, Layout
 };
-use crate::highscore::SaveData;
 
-const MENU: [&'static str; DISP_HEIGHT as usize] = [
+const MENU: [&str; MENU_HEIGHT as usize] = [
     "                      ",
     "╔════════════════════╗",
     "║                    ║",
@@ -35,80 +46,304 @@ const MENU: [&'static str; DISP_HEIGHT as usize] = [
     "║ Created by Dylan T ║",
     "║     circa 2022     ║",
     "║                    ║",
-    "║                    ║",
     "║     Controls:      ║",
-    "║ - a/d - left/right ║",
-    "║   - q/e - rotate   ║",
-    "║ - s -> drop piece  ║",
-    "║    - p -> pause    ║",
-    "║   - back -> quit   ║",
     "║                    ║",
     "║                    ║",
-    "║  Enter to begin... ║",
     "║                    ║",
     "║                    ║",
     "║                    ║",
+    "║                    ║",
+    "║                    ║",
+    "║                    ║",
+    "║                    ║",
+    "║                    ║",
+    "║                    ║",
+    "║   Level:           ║",
     "║     High Score:    ║",
     "║                    ║",
     "║                    ║",
     "╚════════════════════╝",
+    "                      ",
     "                      "
 ];
+const MENU_WIDTH: u16 = 22;
+const MENU_HEIGHT: u16 = 26;
 const MENU_COLOR: &dyn Color = &White;
+const MENU_INNER_WIDTH: usize = 20;
+const MENU_CONTROLS_Y: u16 = 9;
+const MENU_ACTIONS_Y: [u16; Action::MENU.len()] = [ 19, 23 ]; // Rows for each menu action's help
+const MENU_LEVEL_POS: (u16, u16) = (12, 20);
+
+// Options passed on the command line
+struct Args {
+    seed: Option<u64>,
+    randomizer: Option<RandomizerKind>,
+    record: Option<String>, // Save each game's replay here
+    replay: Option<String> // Watch this replay instead of playing
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut parsed = Self {
+            seed: None,
+            randomizer: None,
+            record: None,
+            replay: None
+        };
+
+        let mut args = args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("Missing value for --seed")?;
+                    let seed = value.parse().map_err(|_| format!("Invalid seed '{}'", value))?;
+                    parsed.seed = Some(seed);
+                }, "--randomizer" => {
+                    let value = args.next().ok_or("Missing value for --randomizer")?;
+                    parsed.randomizer = Some(value.parse()?);
+                }, "--record" => {
+                    parsed.record = Some(args.next().ok_or("Missing file for --record")?);
+                }, "--replay" => {
+                    parsed.replay = Some(args.next().ok_or("Missing file for --replay")?);
+                }, _ => return Err(format!("Unknown argument '{}'", arg))
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn draw_menu(cnv: &mut dyn Draw, keys: &KeyBindings, hs_disp: &Vec<&String>, level: u64) {
+    let level_str = format!("< {:02} >", level);
+    let game_help = controls_help(keys, &Action::IN_GAME, MENU_INNER_WIDTH);
+    let menu_help = controls_help(keys, &Action::MENU, MENU_INNER_WIDTH);
+
+    cnv.draw_strs(&MENU.to_vec(), (1, 1), MENU_COLOR, &Reset);
+    cnv.draw_strings(&game_help.iter().collect(), (2, MENU_CONTROLS_Y), MENU_COLOR, &Reset);
+    for (line, y) in menu_help.iter().zip(MENU_ACTIONS_Y) {
+        cnv.draw_strings(&vec![ line ], (2, y), MENU_COLOR, &Reset);
+    }
+    cnv.draw_strings(hs_disp, (2, 22), MENU_COLOR, &Reset);
+    cnv.draw_strings(&vec![ &level_str ], MENU_LEVEL_POS, MENU_COLOR, &Reset);
+    cnv.flush();
+}
 
 fn main() {
-    // Load high score from config file
-    let save = SaveData::load_config();
-    let mut high_score = save.assert_hs();
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(msg) => {
+            println!("{}", msg);
+            println!(
+                "Usage: tetris-cli [--seed <number>] [--randomizer <{}>] [--record <file>]",
+                RandomizerKind::NAMES.join("|")
+            );
+            println!("       tetris-cli --replay <file>");
+            return;
+        }
+    };
+
+    // Load high scores and settings from config file
+    let mut save = match SaveData::load_config() {
+        Ok(save) => save,
+        Err(msg) => {
+            println!("Cannot start game! Error in config file:");
+            println!("  {}", msg);
+            return;
+        }
+    };
+
+    // Command line options win over the config file
+    let mut rules = save.rules.clone();
+    if let Some(randomizer) = args.randomizer {
+        rules.randomizer = randomizer;
+    }
+    let mut rules = rules.clamped();
+
+    // Keys bound twice would silently do only one thing, so refuse to start
+    let conflicts = save.keys.conflicts();
+    if !conflicts.is_empty() {
+        println!("Cannot start game! Conflicting key bindings in config file:");
+        for conflict in conflicts {
+            println!("  {}", conflict);
+        }
+        return;
+    }
+
+    if let Some(path) = &args.replay {
+        match Replay::load(path) {
+            Ok(replay) => show_replay(replay, &save),
+            Err(msg) => println!("{}", msg)
+        }
+        return;
+    }
 
-    // Check that terminal is big enough
+    // Check that terminal is big enough for the menu and a game with this size of well
+    let layout = Layout::new(&rules);
+    let min_width = layout.width.max(MENU_WIDTH).max(TABLE_WIDTH);
+    let min_height = layout.height.max(MENU_HEIGHT).max(TABLE_HEIGHT);
     let (width, height) = terminal_size().unwrap();
-    if width < DISP_WIDTH || height < DISP_HEIGHT {
+    if width < min_width || height < min_height {
         println!(
             "Cannot start game! Terminal window too small. Must be at least {}x{}",
-            DISP_WIDTH, DISP_HEIGHT
+            min_width, min_height
         );
         return;
     }
 
     let mut cnv = Canvas::new();
+    cnv.place(MENU_WIDTH, save.display.anchor);
     let mut inp = KeyReader::new();
 
     // Show the menu and controls before launching the game
-    let mut last_time = Instant::now();
-    let interval_ms = 1_000 / FPS;
+    let mut clock = Clock::new();
+    let mut record_error = None;
+    let mut name = String::new(); // Last name put in the high score table
     loop {
-        // Keep stable fps
-        let now = Instant::now();
-        let delta_time_ms =
-            (now.duration_since(last_time).subsec_nanos() / 1_000_000) as u64;
-        if delta_time_ms < interval_ms {
-            sleep(Duration::from_millis(interval_ms - delta_time_ms));
+        // Redraw once per tick
+        if clock.ticks_due() == 0 {
+            sleep(clock.until_next_tick());
             continue;
         }
-        last_time = now;
+
+        cnv.resized();
+        if !wait_for_room(&mut cnv, &mut inp, &save.keys, (MENU_WIDTH, MENU_HEIGHT)) {
+            break;
+        }
 
         // Conver the loaded high score into a string so we can draw it
-        let hs_str = format!("{:020}", high_score);
+        let hs_str = format!("{:020}", save.best_score());
         let hs_disp = vec![ &hs_str ];
+        draw_menu(&mut cnv, &save.keys, &hs_disp, rules.start_level);
 
-        cnv.draw_strs(&MENU.to_vec(), (1, 1), &MENU_COLOR, &Reset);
-        cnv.draw_strings(&hs_disp, (2, 22), &MENU_COLOR, &Reset);
-        cnv.flush();
-
-        let key = inp.get_key();
-        match key {
-            b'\n' | b'\r' => { // Enter (i.e. start game)
-                let mut state = GameState::new();
-                let new_score = state.play(&mut cnv, &mut inp, &hs_disp);
-                if new_score > high_score {
-                    high_score = new_score;
-                    SaveData::save_value(high_score);
+        match inp.get_key().and_then(|key| save.keys.action(key)) {
+            Some(Action::Start) => {
+                // Same seed every game if one was given, otherwise a fresh one
+                let seed = args.seed.unwrap_or_else(rand::random);
+                let mut state = GameState::new(seed, rules.clone());
+                let mut replay = Replay::new(seed, rules.clone());
+                cnv.clear(); // The menu and the game are different sizes
+                let new_score = play(
+                    &mut state, &mut replay, &mut cnv, &mut inp,
+                    &hs_disp, &save.display, &save.keys
+                );
+                if let Some(path) = &args.record {
+                    // Can't print over the game, so only the last failure gets shown at exit
+                    record_error = replay.save(path).err();
+                }
+                cnv.clear();
+                if let Some(rank) = save.rank(new_score) {
+                    name = enter_name(
+                        &mut cnv, &mut inp, &save.keys, rank, &name, save.display.anchor
+                    );
+                    let rank = save.insert(HighScore::new(&name, &state));
+                    save.store_config();
+                    cnv.clear();
+                    show_high_scores(
+                        &mut cnv, &mut inp, &save.keys,
+                        save.high_scores(), rank, save.display.anchor
+                    );
+                    cnv.clear();
                 }
-            }, 127 => break, // Backspace
+                cnv.place(MENU_WIDTH, save.display.anchor);
+                clock.reset();
+            }, Some(Action::Scores) => {
+                cnv.clear();
+                show_high_scores(
+                    &mut cnv, &mut inp, &save.keys, save.high_scores(), None, save.display.anchor
+                );
+                cnv.clear();
+                cnv.place(MENU_WIDTH, save.display.anchor);
+                clock.reset();
+            }, Some(Action::Left) => { // Pick the starting level with the move keys
+                rules.start_level = rules.start_level.max(2) - 1;
+            }, Some(Action::Right) => {
+                rules.start_level = (rules.start_level + 1).min(MAX_START_LEVEL);
+            }, Some(Action::Quit) => break,
             _ => {}
         }
     }
 
     cnv.reset();
+    if let Some(msg) = record_error {
+        println!("Couldn't save replay: {}", msg);
+    }
+}
+
+// Watch a saved game with its own rules instead of the config file's
+fn show_replay(replay: Replay, save: &SaveData) {
+    let layout = Layout::new(&replay.rules);
+    let (width, height) = terminal_size().unwrap();
+    if width < layout.width || height < layout.height {
+        println!(
+            "Cannot play replay! Terminal window too small. Must be at least {}x{}",
+            layout.width, layout.height
+        );
+        return;
+    }
+
+    let mut cnv = Canvas::new();
+    let mut inp = KeyReader::new();
+    let hs_str = format!("{:020}", save.best_score());
+    let mut playback = Playback::new(replay);
+    watch(&mut playback, &mut cnv, &mut inp, &vec![ &hs_str ], &save.display, &save.keys);
+    cnv.reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use tetris_cli::config::{
+        Key, KeyBindings
+    };
+    use crate::io::Headless;
+    use super::*;
+
+    #[test]
+    fn menu_shows_controls_level_and_high_score() {
+        let hs_str = format!("{:020}", 4321);
+        let mut cnv = Headless::new((MENU_WIDTH, MENU_HEIGHT));
+        draw_menu(&mut cnv, &KeyBindings::default(), &vec![ &hs_str ], 3);
+
+        assert_eq!(cnv.lines(), [
+            "",
+            "╔════════════════════╗",
+            "║                    ║",
+            "║  T E T R I S  CLI  ║",
+            "║ Created by Dylan T ║",
+            "║     circa 2022     ║",
+            "║                    ║",
+            "║     Controls:      ║",
+            "║ Left        a/left ║",
+            "║ Right      d/right ║",
+            "║ Rotate L         q ║",
+            "║ Rotate R      e/up ║",
+            "║ Soft drop   s/down ║",
+            "║ Hard drop    space ║",
+            "║ Hold             w ║",
+            "║ Pause        p/esc ║",
+            "║ Quit     backspace ║",
+            "║                    ║",
+            "║ Start        enter ║",
+            "║   Level: < 03 >    ║",
+            "║     High Score:    ║",
+            "║00000000000000004321║",
+            "║ High scores    tab ║",
+            "╚════════════════════╝",
+            "",
+            ""
+        ]);
+    }
+
+    #[test]
+    fn menu_shows_rebound_menu_keys() {
+        let keys = KeyBindings {
+            start: vec![ Key::Char('n') ],
+            scores: vec![ Key::F(2), Key::Char('h') ],
+            ..KeyBindings::default()
+        };
+        let mut cnv = Headless::new((MENU_WIDTH, MENU_HEIGHT));
+        draw_menu(&mut cnv, &keys, &vec![], 1);
+
+        assert_eq!(cnv.lines()[18], "║ Start            n ║");
+        assert_eq!(cnv.lines()[22], "║ High scores   f2/h ║");
+    }
 }
@@ -0,0 +1,200 @@
+/*
+ * Author: Dylan Turner
+ * Description: Different ways of picking the next piece
+ */
+
+use std::{
+    fmt, str::FromStr
+};
+use rand::{
+    seq::SliceRandom, Rng, RngCore
+};
+use serde::{
+    Serialize, Deserialize
+};
+use crate::tetromino::ShapeType;
+
+const TGM_HISTORY_LEN: usize = 4;
+const TGM_REROLLS: usize = 6;
+
+// Picks shapes from the game's rng. Implementations may keep their own state
+pub trait Randomizer {
+    fn next(&mut self, rng: &mut dyn RngCore) -> ShapeType;
+}
+
+// Selectable randomizers for the config file and command line
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RandomizerKind {
+    #[default]
+    Bag7,
+    Bag14,
+    Tgm,
+    Random
+}
+
+impl RandomizerKind {
+    pub const NAMES: [&'static str; 4] = [ "bag7", "bag14", "tgm", "random" ];
+
+    pub fn create(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::Bag7 => Box::new(Bag::new(1)),
+            RandomizerKind::Bag14 => Box::new(Bag::new(2)),
+            RandomizerKind::Tgm => Box::new(TgmHistory::new()),
+            RandomizerKind::Random => Box::new(Uniform)
+        }
+    }
+}
+
+impl FromStr for RandomizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bag7" => Ok(RandomizerKind::Bag7),
+            "bag14" => Ok(RandomizerKind::Bag14),
+            "tgm" => Ok(RandomizerKind::Tgm),
+            "random" => Ok(RandomizerKind::Random),
+            _ => Err(format!(
+                "Unknown randomizer '{}'. Expected one of: {}", s, RandomizerKind::NAMES.join(", ")
+            ))
+        }
+    }
+}
+
+impl fmt::Display for RandomizerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", RandomizerKind::NAMES[*self as usize])
+    }
+}
+
+// Every shape equally likely every time (the original behavior)
+pub struct Uniform;
+
+impl Randomizer for Uniform {
+    fn next(&mut self, rng: &mut dyn RngCore) -> ShapeType {
+        rng.gen()
+    }
+}
+
+// Shuffle a bag with each shape in it `copies` times, then deal it out
+pub struct Bag {
+    copies: usize,
+    pieces: Vec<ShapeType>
+}
+
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        Self {
+            copies,
+            pieces: Vec::with_capacity(copies * ShapeType::ALL.len())
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut dyn RngCore) -> ShapeType {
+        if self.pieces.is_empty() {
+            for _ in 0..self.copies {
+                self.pieces.extend_from_slice(&ShapeType::ALL);
+            }
+            self.pieces.shuffle(rng);
+        }
+        self.pieces.pop().unwrap()
+    }
+}
+
+/*
+ * TGM style: remember the last 4 pieces and reroll a few times if we land on one of them.
+ * Starts with a history of S and Z and never deals S, Z or O first
+ */
+pub struct TgmHistory {
+    history: [ShapeType; TGM_HISTORY_LEN],
+    first: bool
+}
+
+impl TgmHistory {
+    pub fn new() -> Self {
+        Self {
+            history: [ ShapeType::Z, ShapeType::S, ShapeType::Z, ShapeType::S ],
+            first: true
+        }
+    }
+}
+
+impl Default for TgmHistory {
+    fn default() -> Self {
+        TgmHistory::new()
+    }
+}
+
+impl Randomizer for TgmHistory {
+    fn next(&mut self, rng: &mut dyn RngCore) -> ShapeType {
+        let shape = if self.first {
+            self.first = false;
+            *[ ShapeType::T, ShapeType::L, ShapeType::Mirror, ShapeType::Line ].choose(rng).unwrap()
+        } else {
+            let mut shape = rng.gen();
+            for _ in 1..TGM_REROLLS {
+                if !self.history.contains(&shape) {
+                    break;
+                }
+                shape = rng.gen();
+            }
+            shape
+        };
+
+        self.history.rotate_left(1);
+        self.history[TGM_HISTORY_LEN - 1] = shape;
+        shape
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use super::*;
+
+    fn deal(kind: RandomizerKind, seed: u64, count: usize) -> Vec<ShapeType> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut randomizer = kind.create();
+        (0..count).map(|_| randomizer.next(&mut rng)).collect()
+    }
+
+    #[test]
+    fn bags_deal_each_shape_copies_times() {
+        for (kind, copies) in [ (RandomizerKind::Bag7, 1), (RandomizerKind::Bag14, 2) ] {
+            let bag_len = copies * ShapeType::ALL.len();
+            for seed in 0..20 {
+                for bag in deal(kind, seed, bag_len * 5).chunks(bag_len) {
+                    let mut counts = [ 0; 7 ];
+                    for shape in bag {
+                        counts[*shape as usize] += 1;
+                    }
+                    assert_eq!(counts, [ copies; 7 ], "{} seed {}", kind, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tgm_never_starts_with_s_z_or_o() {
+        for seed in 0..200 {
+            let first = deal(RandomizerKind::Tgm, seed, 1)[0];
+            assert!(
+                ![ ShapeType::S, ShapeType::Z, ShapeType::Square ].contains(&first),
+                "Seed {} started with {:?}", seed, first
+            );
+        }
+    }
+
+    #[test]
+    fn same_seed_deals_the_same_pieces() {
+        for name in RandomizerKind::NAMES {
+            let kind = name.parse().unwrap();
+            assert_eq!(deal(kind, 1_234, 100), deal(kind, 1_234, 100), "{}", name);
+            assert_ne!(deal(kind, 1_234, 100), deal(kind, 4_321, 100), "{}", name);
+        }
+    }
+}
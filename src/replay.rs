@@ -0,0 +1,141 @@
+/*
+ * Author: Dylan Turner
+ * Description: Record the inputs of a game and play them back through a fresh GameState
+ */
+
+use std::fs;
+use serde::{
+    Serialize, Serializer, Deserialize, Deserializer, de
+};
+use crate::game::{
+    GameState, Input, Ruleset, UpdateEndState
+};
+
+pub const REPLAY_VERSION: u32 = 1;
+
+// An input and the tick it came on, i.e. how many updates had run before it
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct TimedInput {
+    pub tick: u64,
+    pub input: Input
+}
+
+// Everything needed to play a game again exactly. Saved as toml
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub version: u32,
+    #[serde(serialize_with = "seed_to_text", deserialize_with = "seed_from_text")]
+    pub seed: u64,
+    pub length: u64, // Ticks until the game was lost or quit
+    pub inputs: Vec<TimedInput>, // Before rules, since toml can't write [] after a table
+    pub rules: Ruleset
+}
+
+impl Replay {
+    pub fn new(seed: u64, rules: Ruleset) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            length: 0,
+            inputs: Vec::new(),
+            rules
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, input: Input) {
+        self.inputs.push(TimedInput { tick, input });
+    }
+
+    // Mark where the game stopped, so playback can stop there too
+    pub fn finish(&mut self, ticks: u64) {
+        self.length = ticks;
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Can't read '{}': {}", path, err))?;
+        let mut replay: Replay = toml::from_str(&text)
+            .map_err(|err| format!("'{}' isn't a replay: {}", path, err))?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!(
+                "'{}' is a version {} replay, but only version {} can be played",
+                path, replay.version, REPLAY_VERSION
+            ));
+        }
+        replay.rules = replay.rules.clamped(); // Same as rules from the config file
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|err| err.to_string())?;
+        fs::write(path, text).map_err(|err| format!("Can't write '{}': {}", path, err))
+    }
+}
+
+// Toml integers are signed, so seeds are written as text to fit every u64
+fn seed_to_text<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(seed)
+}
+
+fn seed_from_text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    text.parse().map_err(de::Error::custom)
+}
+
+// Steps a fresh game through a replay's inputs one tick at a time
+pub struct Playback {
+    replay: Replay,
+    state: GameState,
+    next: usize, // Index of the next input to apply
+    over: bool
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        let state = GameState::new(replay.seed, replay.rules.clone());
+        Self {
+            replay,
+            state,
+            next: 0,
+            over: false
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    // The game was lost, or got to where the player quit
+    pub fn is_over(&self) -> bool {
+        self.over
+    }
+
+    // Apply the inputs from this tick then update, the same way a front end does when playing.
+    // Does nothing once the game is over
+    pub fn step(&mut self) {
+        if self.over {
+            return;
+        }
+
+        while let Some(timed) = self.replay.inputs.get(self.next) {
+            if timed.tick > self.state.ticks() {
+                break;
+            }
+            self.next += 1;
+            if self.state.handle_input(timed.input) == UpdateEndState::Lost {
+                self.over = true;
+                return;
+            }
+        }
+
+        if self.state.ticks() >= self.replay.length {
+            self.over = true;
+            return;
+        }
+        self.over = self.state.update() == UpdateEndState::Lost;
+    }
+}
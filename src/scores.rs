@@ -0,0 +1,223 @@
+/*
+ * Author: Dylan Turner
+ * Description: High score table screen and the name prompt for games that make it in
+ */
+
+use std::thread::sleep;
+use termion::color::{
+    Color, White, LightYellow, Reset
+};
+use tetris_cli::{
+    clock::Clock,
+    config::{
+        Action, Anchor, Key, KeyBindings
+    }, highscore::{
+        HighScore, DEFAULT_NAME, MAX_HIGH_SCORES, MAX_NAME_LEN
+    }, input::KeySource
+};
+use crate::io::Draw;
+use crate::tui::{
+    put_box, put_text, start_footer, wait_for_room
+};
+
+pub const TABLE_WIDTH: u16 = 46;
+pub const TABLE_HEIGHT: u16 = 26;
+const TABLE_INNER_WIDTH: usize = 42; // Inside the border with a space either side
+const TABLE_ROWS_Y: u16 = 5;
+const TABLE_COLOR: &dyn Color = &White;
+const NEW_ENTRY_COLOR: &dyn Color = &LightYellow;
+
+const PROMPT: [&str; 10] = [
+    "╔════════════════════╗",
+    "║  NEW HIGH SCORE!   ║",
+    "║                    ║",
+    "║                    ║",
+    "║                    ║",
+    "║ Name:              ║",
+    "║                    ║",
+    "║                    ║",
+    "║   Enter to save    ║",
+    "╚════════════════════╝"
+];
+const PROMPT_WIDTH: u16 = 22;
+const PROMPT_INNER_WIDTH: usize = 20;
+const PROMPT_RANK_Y: u16 = 4;
+const PROMPT_NAME_Y: u16 = 7;
+
+// Border, column names and footer, with room for two rows per entry
+fn table_border(keys: &KeyBindings) -> Vec<String> {
+    let mut grid = vec![ vec![ ' '; TABLE_WIDTH as usize ]; TABLE_HEIGHT as usize ];
+    put_box(&mut grid, (1, 1), (TABLE_WIDTH, TABLE_HEIGHT), " HIGH SCORES ");
+    put_text(&mut grid, (3, 2), &format!("{:>2} {:<10} {:>28}", "#", "Name", "Score"));
+    put_text(
+        &mut grid, (3, 3),
+        &format!("   {:<10} {:<12} {:>3} {:>5} {:>5}", "Date", "Mode", "Lv", "Lines", "Time")
+    );
+    put_text(&mut grid, (3, 4), &"─".repeat(TABLE_INNER_WIDTH));
+    put_text(&mut grid, (3, TABLE_HEIGHT - 1), &start_footer(keys, "go back", TABLE_INNER_WIDTH));
+    grid.iter().map(|row| row.iter().collect()).collect()
+}
+
+// Two rows per entry, cut to fit inside the table
+fn entry_lines(rank: usize, entry: &HighScore) -> [String; 2] {
+    let time = format!("{}:{:02}", entry.duration_secs / 60, entry.duration_secs % 60);
+    let lines = [
+        format!("{:>2} {:<10} {:>28}", rank + 1, entry.name, entry.score),
+        format!(
+            "   {:<10} {:<12} {:>3} {:>5} {:>5}",
+            entry.date, entry.mode, entry.level, entry.lines, time
+        )
+    ];
+    lines.map(|line| line.chars().take(TABLE_INNER_WIDTH).collect())
+}
+
+pub fn draw_high_scores(
+        cnv: &mut dyn Draw, keys: &KeyBindings, entries: &[HighScore], new_entry: Option<usize>) {
+    cnv.draw_strings(&table_border(keys).iter().collect(), (1, 1), TABLE_COLOR, &Reset);
+    if entries.is_empty() {
+        let empty_str = format!("{:^1$}", "No high scores yet", TABLE_INNER_WIDTH);
+        cnv.draw_strings(&vec![ &empty_str ], (3, TABLE_ROWS_Y), TABLE_COLOR, &Reset);
+    }
+    for (rank, entry) in entries.iter().enumerate() {
+        let color = if new_entry == Some(rank) {
+            NEW_ENTRY_COLOR
+        } else {
+            TABLE_COLOR
+        };
+        let lines = entry_lines(rank, entry);
+        let y = TABLE_ROWS_Y + rank as u16 * 2;
+        cnv.draw_strings(&lines.iter().collect(), (3, y), color, &Reset);
+    }
+    cnv.flush();
+}
+
+// Show the table until start or quit is pressed. The new entry, if any, is highlighted
+pub fn show_high_scores(
+        cnv: &mut dyn Draw, inp: &mut dyn KeySource, keys: &KeyBindings,
+        entries: &[HighScore], new_entry: Option<usize>, anchor: Anchor) {
+    cnv.place(TABLE_WIDTH, anchor);
+    loop {
+        cnv.resized();
+        if !wait_for_room(cnv, inp, keys, (TABLE_WIDTH, TABLE_HEIGHT)) {
+            return;
+        }
+        draw_high_scores(cnv, keys, entries, new_entry);
+
+        match inp.get_key().and_then(|key| keys.action(key)) {
+            Some(Action::Start) | Some(Action::Quit) => return,
+            _ => sleep(Clock::tick_length())
+        }
+    }
+}
+
+pub fn draw_name_prompt(cnv: &mut dyn Draw, rank: usize, name: &str) {
+    let rank_str = format!("#{} of {}", rank + 1, MAX_HIGH_SCORES);
+    let rank_str = format!("{:^1$}", rank_str, PROMPT_INNER_WIDTH);
+    let name_str = format!("{:<1$}", format!(" {}_", name), PROMPT_INNER_WIDTH);
+    cnv.draw_strs(&PROMPT.to_vec(), (1, 1), TABLE_COLOR, &Reset);
+    cnv.draw_strings(&vec![ &rank_str ], (2, PROMPT_RANK_Y), NEW_ENTRY_COLOR, &Reset);
+    cnv.draw_strings(&vec![ &name_str ], (2, PROMPT_NAME_Y), NEW_ENTRY_COLOR, &Reset);
+    cnv.flush();
+}
+
+// Ask for a name to put in the table, starting from the last one used. Every key types here,
+// so it always finishes with enter rather than the start keys, which may be letters. A blank
+// name becomes the default
+pub fn enter_name(
+        cnv: &mut dyn Draw, inp: &mut dyn KeySource, keys: &KeyBindings,
+        rank: usize, name: &str, anchor: Anchor) -> String {
+    let mut name = String::from(name);
+    cnv.place(PROMPT_WIDTH, anchor);
+    loop {
+        cnv.resized();
+        if !wait_for_room(cnv, inp, keys, (PROMPT_WIDTH, PROMPT.len() as u16)) {
+            break;
+        }
+        draw_name_prompt(cnv, rank, &name);
+
+        match inp.get_key() {
+            Some(Key::Enter) => break,
+            Some(Key::Backspace) => {
+                name.pop();
+            }, Some(Key::Char(c)) if !c.is_control() && name.chars().count() < MAX_NAME_LEN => {
+                name.push(c);
+            }, _ => sleep(Clock::tick_length())
+        }
+    }
+
+    match name.trim() {
+        "" => String::from(DEFAULT_NAME),
+        trimmed => String::from(trimmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use termion::color::Fg;
+    use tetris_cli::input::Scripted;
+    use crate::io::Headless;
+    use super::*;
+
+    fn entry(name: &str, score: u64) -> HighScore {
+        HighScore {
+            name: String::from(name),
+            score,
+            lines: 42,
+            level: 5,
+            duration_secs: 754,
+            date: String::from("2026-10-18"),
+            mode: String::from("bag7 10x20")
+        }
+    }
+
+    #[test]
+    fn table_shows_entries_best_first() {
+        let mut cnv = Headless::new((TABLE_WIDTH, TABLE_HEIGHT));
+        let entries = [ entry("Dylan", 12_345), entry("Sam", 900) ];
+        draw_high_scores(&mut cnv, &KeyBindings::default(), &entries, Some(1));
+
+        assert_eq!(cnv.lines()[..8], [
+            "╔═══════════════ HIGH SCORES ════════════════╗",
+            "║  # Name                              Score ║",
+            "║    Date       Mode          Lv Lines  Time ║",
+            "║ ────────────────────────────────────────── ║",
+            "║  1 Dylan                             12345 ║",
+            "║    2026-10-18 bag7 10x20     5    42 12:34 ║",
+            "║  2 Sam                                 900 ║",
+            "║    2026-10-18 bag7 10x20     5    42 12:34 ║"
+        ]);
+        assert_eq!(cnv.lines()[24], "║              Enter to go back              ║");
+        assert_eq!(cnv.fg((4, 7)), Fg(NEW_ENTRY_COLOR).to_string());
+        assert_eq!(cnv.fg((4, 5)), Fg(TABLE_COLOR).to_string());
+    }
+
+    #[test]
+    fn table_goes_back_with_the_start_keys() {
+        let keys = KeyBindings {
+            start: vec![ Key::F(2), Key::Char('n') ],
+            ..KeyBindings::default()
+        };
+        let mut inp = Scripted::new(vec![
+            (0, Key::Enter), (1, Key::Char('n')), (2, Key::Backspace)
+        ]);
+        let mut cnv = Headless::new((TABLE_WIDTH, TABLE_HEIGHT));
+        show_high_scores(&mut cnv, &mut inp, &keys, &[], None, Anchor::Left);
+
+        assert!(!inp.is_done()); // Left before the quit key
+        assert_eq!(cnv.lines()[24], "║              F2/n to go back               ║");
+    }
+
+    #[test]
+    fn names_are_typed_with_any_key() {
+        let mut inp = Scripted::new(vec![
+            (0, Key::Char('a')), (1, Key::Char('w')), (2, Key::Char('x')), (3, Key::Backspace),
+            (4, Key::Char(' ')), (5, Key::Char('p')), (6, Key::Enter)
+        ]);
+        let mut cnv = Headless::new((TABLE_WIDTH, TABLE_HEIGHT));
+        let name = enter_name(&mut cnv, &mut inp, &KeyBindings::default(), 2, "Jo", Anchor::Left);
+
+        assert_eq!(name, "Joaw p");
+        assert_eq!(cnv.lines()[3], "║      #3 of 10      ║");
+        assert_eq!(cnv.lines()[6], "║ Joaw p_            ║");
+    }
+}
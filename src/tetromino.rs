@@ -6,91 +6,160 @@
 use rand::{
     distributions::{
         Distribution, Standard
-    }, Rng, random
-};
-use termion::color::{
-    Color, Magenta, Green, Yellow, Blue, Cyan, LightYellow, LightRed,
+    }, Rng
 };
 use crate::game::Dir;
 
 /*
- * Shape could be -2 to 2 in all directions (bc rotations)
- * so needs to be 3x4 shape
- *      -2  -1  0   1    2
- *    _____________________
- * -2 |   |   |   |   |   |
- *    _____________________
- * -1 |   |   |   |   |   |
- *    ---------------------
- *  0 |   |   |   |   |   |
- *    ---------------------
- *  1 |   |   |   |   |   |
- *    ---------------------
- *  2 |   |   |   |   |   |
- *    ---------------------
+ * Shapes in their spawn state (flat side down, as in SRS) around the piece's position (0, 0).
+ * Rows count down, so -1 is the row above the position
+ *      -1  0   1   2
+ *    -----------------
+ * -1 |   |   |   |   |
+ *    -----------------
+ *  0 |   | x |   |   |
+ *    -----------------
+ *  1 |   |   |   |   |
+ *    -----------------
+ * J, L, S, T and Z turn around (0, 0). I and O turn around the corner of a cell, so their
+ * centers are given doubled to stay in whole numbers
  */
 const SHAPE_COORDS: [[(i16, i16); 4]; 7] = [
-    [ (-1,  0), (0,  0), ( 1, 0), ( 0, 1) ],
-    [ (-1, -1), (0, -1), ( 0, 0), ( 0, 1) ],
-    [ ( 1, -1), (0, -1), ( 0, 0), ( 0, 1) ],
-    [ ( 0, -1), (0,  0), ( 1, 0), ( 1, 1) ],
-    [ ( 0, -1), (0,  0), (-1, 0), (-1, 1) ],
-    [ ( 0,  0), (1,  0), ( 0, 1), ( 1, 1) ],
-    [ ( 0, -1), (0,  0), ( 0, 1), ( 0, 2) ]
+    [ (-1,  0), (0,  0), ( 1,  0), ( 0, -1) ],
+    [ (-1,  0), (0,  0), ( 1,  0), ( 1, -1) ],
+    [ (-1, -1), (-1, 0), ( 0,  0), ( 1,  0) ],
+    [ (-1,  0), (0,  0), ( 0, -1), ( 1, -1) ],
+    [ (-1, -1), (0, -1), ( 0,  0), ( 1,  0) ],
+    [ ( 0, -1), (1, -1), ( 0,  0), ( 1,  0) ],
+    [ (-1,  0), (0,  0), ( 1,  0), ( 2,  0) ]
+];
+const ROTATION_CENTERS_X2: [(i16, i16); 7] = [
+    (0, 0), (0, 0), (0, 0), (0, 0), (0, 0), (1, -1), (1, 1)
+];
+
+/*
+ * SRS wall kicks, tried in order until one fits. Rows go 0 -> R, 0 -> L, R -> 2, R -> 0,
+ * 2 -> L, 2 -> R, L -> 0, L -> 2. The y values are flipped from the usual tables
+ * since our rows count down
+ */
+const JLSTZ_KICKS: [[(i16, i16); 5]; 8] = [
+    [ ( 0,  0), (-1,  0), (-1, -1), ( 0,  2), (-1,  2) ],
+    [ ( 0,  0), ( 1,  0), ( 1, -1), ( 0,  2), ( 1,  2) ],
+    [ ( 0,  0), ( 1,  0), ( 1,  1), ( 0, -2), ( 1, -2) ],
+    [ ( 0,  0), ( 1,  0), ( 1,  1), ( 0, -2), ( 1, -2) ],
+    [ ( 0,  0), ( 1,  0), ( 1, -1), ( 0,  2), ( 1,  2) ],
+    [ ( 0,  0), (-1,  0), (-1, -1), ( 0,  2), (-1,  2) ],
+    [ ( 0,  0), (-1,  0), (-1,  1), ( 0, -2), (-1, -2) ],
+    [ ( 0,  0), (-1,  0), (-1,  1), ( 0, -2), (-1, -2) ]
 ];
-pub const SHAPE_COLORS: [&dyn Color; 7] = [
-    &Magenta, &Yellow, &Blue, &Green, &LightRed, &LightYellow, &Cyan
+const I_KICKS: [[(i16, i16); 5]; 8] = [
+    [ ( 0,  0), (-2,  0), ( 1,  0), (-2,  1), ( 1, -2) ],
+    [ ( 0,  0), (-1,  0), ( 2,  0), (-1, -2), ( 2,  1) ],
+    [ ( 0,  0), (-1,  0), ( 2,  0), (-1, -2), ( 2,  1) ],
+    [ ( 0,  0), ( 2,  0), (-1,  0), ( 2, -1), (-1,  2) ],
+    [ ( 0,  0), ( 2,  0), (-1,  0), ( 2, -1), (-1,  2) ],
+    [ ( 0,  0), ( 1,  0), (-2,  0), ( 1,  2), (-2, -1) ],
+    [ ( 0,  0), ( 1,  0), (-2,  0), ( 1,  2), (-2, -1) ],
+    [ ( 0,  0), (-2,  0), ( 1,  0), (-2,  1), ( 1, -2) ]
 ];
+const O_KICKS: [(i16, i16); 1] = [ (0, 0) ];
+
+// The four SRS rotation states: spawn, one turn right, two turns, one turn left
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Rotation {
+    Spawn,
+    Right,
+    Two,
+    Left
+}
+
+impl Rotation {
+    pub fn turned(self, dir: Dir) -> Self {
+        match (self, dir) {
+            (Rotation::Spawn, Dir::Right) | (Rotation::Two, Dir::Left) => Rotation::Right,
+            (Rotation::Right, Dir::Right) | (Rotation::Left, Dir::Left) => Rotation::Two,
+            (Rotation::Two, Dir::Right) | (Rotation::Spawn, Dir::Left) => Rotation::Left,
+            (Rotation::Left, Dir::Right) | (Rotation::Right, Dir::Left) => Rotation::Spawn,
+            (_, Dir::Down) => self
+        }
+    }
+}
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum ShapeType { T, L, Mirror, S, Z, Square, Line }
 
+impl ShapeType {
+    pub const ALL: [ShapeType; 7] = [
+        ShapeType::T, ShapeType::L, ShapeType::Mirror, ShapeType::S,
+        ShapeType::Z, ShapeType::Square, ShapeType::Line
+    ];
+}
+
 impl Distribution<ShapeType> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> ShapeType {
-        match rng.gen_range(0..7) {
-            0 => ShapeType::T,
-            1 => ShapeType::L,
-            2 => ShapeType::Mirror,
-            3 => ShapeType::S,
-            4 => ShapeType::Z,
-            5 => ShapeType::Square,
-            6 => ShapeType::Line,
-            _ => ShapeType::T // Required by rust but shouldn't happen
-        }
+        ShapeType::ALL[rng.gen_range(0..ShapeType::ALL.len())]
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct Tetromino {
-    pub pos: (f32, f32),
+    pub pos: (i16, i16), // Grid cell of the shape's (0, 0)
     pub shape: ShapeType,
     pub coords: [(i16, i16); 4],
+    pub rotation: Rotation,
     pub fg: usize
 }
 
 impl Tetromino {
-    pub fn select() -> Self {
-        let shape = random();
+    pub fn new(shape: ShapeType) -> Self {
         Self {
-            pos: (4.0, 0.0),
+            pos: (0, 0), // The game puts it at the spawn point when it comes out
             shape,
             coords: SHAPE_COORDS[shape as usize],
+            rotation: Rotation::Spawn,
             fg: shape as usize
         }
     }
 
-    pub fn rotate(&mut self, dir: Dir) {
-        if self.shape == ShapeType::Square {
-            return;
-        }
+    // Grid cells the shape covers at its current position
+    pub fn cells(&self) -> [(i16, i16); 4] {
+        let (shape_x, shape_y) = self.pos;
+        self.coords.map(|(x, y)| (x + shape_x, y + shape_y))
+    }
 
+    // Width and height of the shape in cells
+    pub fn size(&self) -> (i16, i16) {
+        let xs = self.coords.map(|coord| coord.0);
+        let ys = self.coords.map(|coord| coord.1);
+        (
+            xs.iter().max().unwrap() - xs.iter().min().unwrap() + 1,
+            ys.iter().max().unwrap() - ys.iter().min().unwrap() + 1
+        )
+    }
+
+    // Turn the shape in place around its center. Doesn't move or check for collisions
+    pub fn rotate(&mut self, dir: Dir) {
+        let (center_x, center_y) = ROTATION_CENTERS_X2[self.shape as usize];
         for i in 0..4 {
             let (x, y) = self.coords[i];
-            match dir {
-                Dir::Left => self.coords[i] = (y, -x),
-                Dir::Right => self.coords[i] = (-y, x),
-                _ => {}
-            }
+            let (x2, y2) = (x * 2 - center_x, y * 2 - center_y);
+            let (x2, y2) = match dir {
+                Dir::Left => (y2, -x2),
+                Dir::Right => (-y2, x2),
+                Dir::Down => (x2, y2)
+            };
+            self.coords[i] = ((x2 + center_x) / 2, (y2 + center_y) / 2);
+        }
+        self.rotation = self.rotation.turned(dir);
+    }
+
+    // Offsets to try, in order, when turning from the current rotation state
+    pub fn kicks(&self, dir: Dir) -> &'static [(i16, i16)] {
+        let row = self.rotation as usize * 2 + (dir == Dir::Left) as usize;
+        match self.shape {
+            ShapeType::Square => &O_KICKS,
+            ShapeType::Line => &I_KICKS[row],
+            _ => &JLSTZ_KICKS[row]
         }
     }
 }
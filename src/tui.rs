@@ -0,0 +1,594 @@
+/*
+ * Author: Dylan Turner
+ * Description: Terminal front end that drives a GameState with keys and draws it
+ */
+
+use std::thread::sleep;
+use termion::color::{
+    Color, White, LightBlack, Reset, Magenta, Green, Yellow, Blue, Cyan, LightYellow, LightRed
+};
+use tetris_cli::{
+    clock::Clock,
+    config::{
+        Action, Display, KeyBindings
+    },
+    game::{
+        GameState, Ruleset, UpdateEndState, BUFFER_HEIGHT
+    }, input::KeySource, replay::{
+        Playback, Replay
+    }, tetromino::Tetromino
+};
+use crate::io::{
+    Draw, SHAPE_WIDTH, SHAPE_STR
+};
+
+const PAUSE: [&str; 3] = [
+    "╔════════╗",
+    "║ PAUSED ║",
+    "╚════════╝"
+];
+const GAME_OVER: [&str; 7] = [
+    "╔════════════════════╗",
+    "║     GAME  OVER     ║",
+    "║                    ║",
+    "║ Seed:              ║",
+    "║                    ║",
+    "║                    ║",
+    "╚════════════════════╝"
+];
+const GAME_OVER_INNER_WIDTH: usize = 20;
+const TOO_SMALL: [&str; 2] = [
+    "Terminal too small!",
+    "Resize to continue"
+];
+const BORDER_COLOR: &dyn Color = &White;
+const SCORE_COLOR: &dyn Color = &White;
+const HUD_HEIGHT: u16 = 3; // Rows above the well for the scores
+const LEVEL_X: u16 = 36; // Level and lines go right of the scores
+const LEVEL_WIDTH: u16 = 11;
+const SPEEDS: [f64; 6] = [ 0.25, 0.5, 1.0, 2.0, 4.0, 8.0 ]; // Replay speeds to pick from
+const NORMAL_SPEED: usize = 2;
+const SHAPE_DRAW_OFFSET: i16 = 6;
+const PEEK_ROWS: i16 = 1; // Buffer rows shown at the top of the well
+const PANEL_WIDTH: u16 = 12; // Hold and next panels, borders included
+const PANEL_INNER_WIDTH: i16 = 10;
+const HOLD_HEIGHT: u16 = 6;
+const WELL_X: u16 = PANEL_WIDTH + 1; // Column of the well's left wall
+const HOLD_POS: (u16, u16) = (2, 6);
+const NEXT_SPACING: u16 = 3;
+const HOLD_USED_COLOR: &dyn Color = &LightBlack;
+pub const SHAPE_COLORS: [&dyn Color; 7] = [
+    &Magenta, &Yellow, &Blue, &Green, &LightRed, &LightYellow, &Cyan
+];
+
+// Where everything goes on screen, worked out from the size of the well and the preview
+pub struct Layout {
+    pub width: u16,
+    pub height: u16,
+    border: Vec<String>,
+    next_pos: (u16, u16), // Inside the next panel
+    well_center: (u16, u16)
+}
+
+impl Layout {
+    pub fn new(rules: &Ruleset) -> Self {
+        let rules = rules.clone().clamped();
+        let well_width = (rules.width * SHAPE_WIDTH) as u16 + 2;
+        let well_height = rules.height as u16 + PEEK_ROWS as u16 + 2;
+        let next_x = WELL_X + well_width;
+        let next_height = rules.preview as u16 * NEXT_SPACING + 3;
+        let width = (next_x + PANEL_WIDTH - 1).max(LEVEL_X + LEVEL_WIDTH - 1);
+        let height = HUD_HEIGHT + well_height.max(next_height).max(HOLD_HEIGHT);
+
+        let mut grid = vec![ vec![ ' '; width as usize ]; height as usize ];
+        put_text(&mut grid, (WELL_X, 1), "H:");
+        put_text(&mut grid, (WELL_X, 2), "S:");
+        put_text(&mut grid, (WELL_X, 3), "#:");
+        put_text(&mut grid, (LEVEL_X, 1), "Lv:");
+        put_text(&mut grid, (LEVEL_X, 2), "Ln:");
+        put_box(&mut grid, (1, HUD_HEIGHT + 1), (PANEL_WIDTH, HOLD_HEIGHT), " HOLD ");
+        put_box(&mut grid, (WELL_X, HUD_HEIGHT + 1), (well_width, well_height), "");
+        put_box(&mut grid, (next_x, HUD_HEIGHT + 1), (PANEL_WIDTH, next_height), " NEXT ");
+
+        Self {
+            width,
+            height,
+            border: grid.iter().map(|row| row.iter().collect()).collect(),
+            next_pos: (next_x + 1, HUD_HEIGHT + 3),
+            well_center: (WELL_X + well_width / 2, HUD_HEIGHT + 1 + well_height / 2)
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    // Top left corner for a box centered on the well, kept on screen if the well is narrow
+    fn overlay_pos(&self, (box_width, box_height): (u16, u16)) -> (u16, u16) {
+        let max_x = (self.width + 1).saturating_sub(box_width).max(1);
+        let x = self.well_center.0.saturating_sub(box_width / 2).clamp(1, max_x);
+        (x, self.well_center.1 - box_height / 2)
+    }
+}
+
+// Run a game until it's lost or quit, writing its inputs down in replay. Returns the score
+// (0 if quit)
+pub fn play(
+        state: &mut GameState, replay: &mut Replay, cnv: &mut dyn Draw, inp: &mut dyn KeySource,
+        hs_disp: &Vec<&String>, display: &Display, keys: &KeyBindings) -> u64 {
+    let layout = Layout::new(state.rules());
+    cnv.place(layout.width, display.anchor);
+    let mut clock = Clock::new();
+    'game: loop {
+        let ticks = clock.ticks_due();
+        if ticks == 0 {
+            sleep(clock.until_next_tick());
+            continue;
+        }
+
+        // Stop the clock while the terminal is too small to show the game
+        cnv.resized();
+        if !cnv.fits(layout.size()) {
+            if !wait_for_room(cnv, inp, keys, layout.size()) {
+                replay.finish(state.ticks());
+                return 0;
+            }
+            clock.reset();
+        }
+
+        // Simulate every tick that's due, then draw once. One key per tick keeps inputs on ticks
+        for _ in 0..ticks {
+            let action = inp.get_key().and_then(|key| keys.action(key));
+            match action {
+                Some(Action::Quit) => { // Back to menu
+                    replay.finish(state.ticks());
+                    return 0;
+                }, Some(Action::Pause) => {
+                    // Keep the game paused until pause is pressed again
+                    while inp.get_key().and_then(|key| keys.action(key)) != Some(Action::Pause) {
+                        if cnv.resized() {
+                            if !wait_for_room(cnv, inp, keys, layout.size()) {
+                                replay.finish(state.ticks());
+                                return 0;
+                            }
+                            draw(state, &layout, cnv, hs_disp, display);
+                        }
+                        let pos = layout.overlay_pos((PAUSE[0].chars().count() as u16, 3));
+                        cnv.draw_strs(&PAUSE.to_vec(), pos, BORDER_COLOR, &Reset);
+                        cnv.flush();
+                        sleep(Clock::tick_length());
+                    }
+                    clock.reset();
+                    break;
+                }, _ => {}
+            }
+
+            if let Some(input) = action.and_then(Action::input) {
+                replay.record(state.ticks(), input);
+                if state.handle_input(input) == UpdateEndState::Lost {
+                    break 'game;
+                }
+            }
+            if state.update() == UpdateEndState::Lost {
+                break 'game;
+            }
+        }
+        draw(state, &layout, cnv, hs_disp, display);
+    }
+
+    replay.finish(state.ticks());
+    game_over(state, &layout, cnv, inp, hs_disp, display, keys);
+    state.score()
+}
+
+// Play a replay back. Pause stops it, left and right change the speed and soft drop steps one
+// tick at a time while paused
+pub fn watch(
+        playback: &mut Playback, cnv: &mut dyn Draw, inp: &mut dyn KeySource,
+        hs_disp: &Vec<&String>, display: &Display, keys: &KeyBindings) {
+    let layout = Layout::new(playback.state().rules());
+    cnv.place(layout.width, display.anchor);
+    let mut clock = Clock::new();
+    let mut speed = NORMAL_SPEED;
+    let mut paused = false;
+    while !playback.is_over() {
+        cnv.resized();
+        if !cnv.fits(layout.size()) {
+            if !wait_for_room(cnv, inp, keys, layout.size()) {
+                return;
+            }
+            clock.reset();
+        }
+
+        let mut steps = 0;
+        match inp.get_key().and_then(|key| keys.action(key)) {
+            Some(Action::Quit) => return,
+            Some(Action::Pause) => {
+                paused = !paused;
+                clock.reset();
+            }, Some(Action::Left) => {
+                speed = speed.saturating_sub(1);
+                clock.set_speed(SPEEDS[speed]);
+            }, Some(Action::Right) => {
+                speed = (speed + 1).min(SPEEDS.len() - 1);
+                clock.set_speed(SPEEDS[speed]);
+            }, Some(Action::SoftDrop) if paused => steps = 1,
+            _ => {}
+        }
+        if !paused {
+            steps = clock.ticks_due();
+        }
+
+        for _ in 0..steps {
+            playback.step();
+        }
+        draw(playback.state(), &layout, cnv, hs_disp, display);
+        draw_replay_status(cnv, SPEEDS[speed], paused);
+
+        sleep(if paused {
+            Clock::tick_length()
+        } else {
+            clock.until_next_tick()
+        });
+    }
+
+    game_over(playback.state(), &layout, cnv, inp, hs_disp, display, keys);
+}
+
+// Speed and whether it's paused, under the level and lines
+fn draw_replay_status(cnv: &mut dyn Draw, speed: f64, paused: bool) {
+    let pause_str = if paused { "PAUSE" } else { "" };
+    let status = format!("{:>5} {:5}", format!("{}x", speed), pause_str);
+    cnv.draw_strings(&vec![ &status ], (LEVEL_X, 3), SCORE_COLOR, &Reset);
+    cnv.flush();
+}
+
+// Show the seed so the game can be replayed, then wait to go back to the menu
+fn game_over(
+        state: &GameState, layout: &Layout, cnv: &mut dyn Draw, inp: &mut dyn KeySource,
+        hs_disp: &Vec<&String>, display: &Display, keys: &KeyBindings) {
+    let seed_str = format!("{:020}", state.seed());
+    let footer = start_footer(keys, "continue", GAME_OVER_INNER_WIDTH);
+    let (x, y) = layout.overlay_pos((GAME_OVER[0].chars().count() as u16, 7));
+    let mut redraw = true;
+    loop {
+        if cnv.resized() {
+            if !wait_for_room(cnv, inp, keys, layout.size()) {
+                break;
+            }
+            draw(state, layout, cnv, hs_disp, display);
+            redraw = true;
+        }
+        if redraw {
+            cnv.draw_strs(&GAME_OVER.to_vec(), (x, y), BORDER_COLOR, &Reset);
+            cnv.draw_strings(&vec![ &seed_str ], (x + 1, y + 4), SCORE_COLOR, &Reset);
+            cnv.draw_strings(&vec![ &footer ], (x + 1, y + 5), BORDER_COLOR, &Reset);
+            cnv.flush();
+            redraw = false;
+        }
+
+        match inp.get_key().and_then(|key| keys.action(key)) {
+            Some(Action::Start) | Some(Action::Quit) => break,
+            _ => sleep(Clock::tick_length())
+        }
+    }
+}
+
+fn draw(
+        state: &GameState, layout: &Layout, cnv: &mut dyn Draw,
+        hs_disp: &Vec<&String>, display: &Display) {
+    cnv.draw_strings(&layout.border.iter().collect(), (1, 1), BORDER_COLOR, &Reset);
+
+    let score_str = format!("{:020}", state.score());
+    let seed_str = format!("{:020}", state.seed());
+    let score_disp = vec![ &score_str, &seed_str ];
+    cnv.draw_strings(hs_disp, (WELL_X + 2, 1), SCORE_COLOR, &Reset);
+    cnv.draw_strings(&score_disp, (WELL_X + 2, 2), SCORE_COLOR, &Reset);
+
+    let level_str = format!("{:08}", state.level());
+    let lines_str = format!("{:08}", state.lines());
+    cnv.draw_strings(&vec![ &level_str, &lines_str ], (LEVEL_X + 3, 1), SCORE_COLOR, &Reset);
+
+    // Settled blocks, including any in the buffer row peeking over the well
+    let blocks = state.blocks();
+    for y in -PEEK_ROWS..state.height() as i16 {
+        for x in 0..state.width() as i16 {
+            let block = blocks[(y + BUFFER_HEIGHT as i16) as usize][x as usize];
+            if block != -1 {
+                draw_cell(cnv, (x, y), SHAPE_STR, SHAPE_COLORS[block as usize]);
+            }
+        }
+    }
+
+    // Ghost first so the real piece is drawn over it when they overlap
+    let color = SHAPE_COLORS[state.curr_shape().fg];
+    if display.ghost {
+        for cell in state.ghost_cells() {
+            draw_cell(cnv, cell, &display.ghost_glyph, color);
+        }
+    }
+    for cell in state.curr_shape_cells() {
+        draw_cell(cnv, cell, SHAPE_STR, color);
+    }
+
+    if let Some(shape) = state.hold() {
+        let color = if state.can_hold() {
+            SHAPE_COLORS[shape.fg]
+        } else {
+            HOLD_USED_COLOR
+        };
+        draw_panel_shape(cnv, shape, HOLD_POS, color);
+    }
+    for (i, shape) in state.queue().iter().enumerate() {
+        let (x, y) = layout.next_pos;
+        draw_panel_shape(cnv, shape, (x, y + i as u16 * NEXT_SPACING), SHAPE_COLORS[shape.fg]);
+    }
+
+    cnv.flush();
+}
+
+// Hold everything while the terminal is smaller than size, saying how big it needs to be.
+// Returns false if the player quit instead
+pub fn wait_for_room(
+        cnv: &mut dyn Draw, inp: &mut dyn KeySource, keys: &KeyBindings, size: (u16, u16)) -> bool {
+    let need_str = format!("Need {}x{}", size.0, size.1);
+    // Too small to fit means there's no room to move it off the left edge either
+    while !cnv.fits(size) {
+        cnv.draw_strs(&TOO_SMALL.to_vec(), (1, 1), BORDER_COLOR, &Reset);
+        cnv.draw_strings(&vec![ &need_str ], (1, TOO_SMALL.len() as u16 + 1), SCORE_COLOR, &Reset);
+        cnv.flush();
+        if inp.get_key().and_then(|key| keys.action(key)) == Some(Action::Quit) {
+            return false;
+        }
+        sleep(Clock::tick_length());
+        cnv.resized();
+    }
+    true
+}
+
+// One line per action with its keys, sized to fit inside the menu box
+pub fn controls_help(keys: &KeyBindings, actions: &[Action], width: usize) -> Vec<String> {
+    actions.iter().map(|action| {
+        let label = action.label();
+        let names: String = keys.describe(*action).chars().take(width - label.len() - 3).collect();
+        format!(" {}{:>pad$} ", label, names, pad = width - label.len() - 2)
+    }).collect()
+}
+
+// Says which keys go on from a screen, e.g. "Enter to continue", centered and cut to width
+pub fn start_footer(keys: &KeyBindings, what: &str, width: usize) -> String {
+    let text = format!("{} to {}", keys.describe(Action::Start), what);
+    let mut chars = text.chars();
+    let text: String = chars.next().into_iter().flat_map(char::to_uppercase).chain(chars).collect();
+    format!("{:^1$}", text, width).chars().take(width).collect()
+}
+
+// Write text into a character grid at a screen position (1 based)
+pub fn put_text(grid: &mut [Vec<char>], (x, y): (u16, u16), text: &str) {
+    for (i, c) in text.chars().enumerate() {
+        grid[y as usize - 1][x as usize - 1 + i] = c;
+    }
+}
+
+// Draw a double lined box into a character grid with a title centered on the top edge
+pub fn put_box(
+        grid: &mut [Vec<char>], (x, y): (u16, u16), (width, height): (u16, u16), title: &str) {
+    let inner = width as usize - 2;
+    let pad = (inner - title.chars().count()) / 2;
+    let top = format!(
+        "╔{}{}{}╗", "═".repeat(pad), title, "═".repeat(inner - pad - title.chars().count())
+    );
+    put_text(grid, (x, y), &top);
+    for row in y + 1..y + height - 1 {
+        put_text(grid, (x, row), &format!("║{}║", " ".repeat(inner)));
+    }
+    put_text(grid, (x, y + height - 1), &format!("╚{}╝", "═".repeat(inner)));
+}
+
+// Draw one grid cell in the well. Buffer rows above the peek row are hidden
+fn draw_cell(cnv: &mut dyn Draw, (x, y): (i16, i16), glyph: &str, color: &dyn Color) {
+    if y < -PEEK_ROWS {
+        return;
+    }
+    let x = x * SHAPE_WIDTH as i16 + WELL_X as i16 + 1;
+    let y = y + SHAPE_DRAW_OFFSET;
+    cnv.draw_strings(&vec![ &String::from(glyph) ], (x as u16, y as u16), color, &Reset);
+}
+
+// Draw a piece centered in a side panel whose inside starts at pos
+fn draw_panel_shape(cnv: &mut dyn Draw, shape: &Tetromino, pos: (u16, u16), color: &dyn Color) {
+    let (width, _) = shape.size();
+    let min_x = shape.coords.iter().map(|coord| coord.0).min().unwrap();
+    let min_y = shape.coords.iter().map(|coord| coord.1).min().unwrap();
+    let pad = (PANEL_INNER_WIDTH - width * SHAPE_WIDTH as i16) / 2;
+    for (coord_x, coord_y) in shape.coords {
+        let x = pos.0 as i16 + pad + (coord_x - min_x) * SHAPE_WIDTH as i16;
+        let y = pos.1 as i16 + coord_y - min_y;
+
+        cnv.draw_strs(&vec![ SHAPE_STR ], (x as u16, y as u16), color, &Reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use termion::color::Fg;
+    use tetris_cli::{
+        config::{
+            Anchor, Key
+        }, game::Input, input::Scripted
+    };
+    use crate::io::Headless;
+    use super::*;
+
+    fn drawn(state: &GameState, size: (u16, u16), anchor: Anchor) -> Headless {
+        let layout = Layout::new(state.rules());
+        let mut cnv = Headless::new(size);
+        cnv.place(layout.width, anchor);
+        let hs_str = format!("{:020}", 1234);
+        draw(state, &layout, &mut cnv, &vec![ &hs_str ], &Display::default());
+        cnv
+    }
+
+    #[test]
+    fn draws_hud_and_board() {
+        let mut state = GameState::new(5, Ruleset::default());
+        state.handle_input(Input::Left);
+        state.handle_input(Input::HardDrop);
+        state.handle_input(Input::Hold);
+
+        let cnv = drawn(&state, (46, 26), Anchor::Left);
+        assert_eq!(cnv.lines(), [
+            "            H:00000000000000001234 Lv:00000001",
+            "            S:00000000000000000038 Ln:00000000",
+            "            #:00000000000000000005",
+            "╔══ HOLD ══╗╔════════════════════╗╔══ NEXT ══╗",
+            "║          ║║        ████        ║║          ║",
+            "║ ████████ ║║        ████        ║║    ████  ║",
+            "║          ║║                    ║║  ████    ║",
+            "║          ║║                    ║║          ║",
+            "╚══════════╝║                    ║║  ██      ║",
+            "            ║                    ║║  ██████  ║",
+            "            ║                    ║║          ║",
+            "            ║                    ║║    ██    ║",
+            "            ║                    ║║  ██████  ║",
+            "            ║                    ║║          ║",
+            "            ║                    ║║  ████    ║",
+            "            ║                    ║║    ████  ║",
+            "            ║                    ║║          ║",
+            "            ║                    ║║ ████████ ║",
+            "            ║                    ║║          ║",
+            "            ║                    ║║          ║",
+            "            ║                    ║╚══════════╝",
+            "            ║        ░░░░        ║",
+            "            ║        ░░░░        ║",
+            "            ║        ██          ║",
+            "            ║    ██████          ║",
+            "            ╚════════════════════╝"
+        ]);
+
+        // Locked blocks keep their piece's color and the hold panel greys out once used
+        assert_eq!(cnv.fg((18, 25)), Fg(SHAPE_COLORS[1]).to_string());
+        assert_eq!(cnv.fg((3, 6)), Fg(HOLD_USED_COLOR).to_string());
+    }
+
+    #[test]
+    fn layout_follows_well_size() {
+        let rules = Ruleset { width: 4, height: 6, preview: 1, ..Ruleset::default() };
+        let state = GameState::new(5, rules);
+
+        let cnv = drawn(&state, (46, 13), Anchor::Left);
+        assert_eq!(cnv.lines()[3..], [
+            "╔══ HOLD ══╗╔════════╗╔══ NEXT ══╗",
+            "║          ║║    ██  ║║          ║",
+            "║          ║║██████  ║║ ████████ ║",
+            "║          ║║        ║║          ║",
+            "║          ║║        ║║          ║",
+            "╚══════════╝║        ║╚══════════╝",
+            "            ║    ░░  ║",
+            "            ║░░░░░░  ║",
+            "            ╚════════╝",
+            ""
+        ]);
+    }
+
+    #[test]
+    fn anchors_in_wide_terminals() {
+        let state = GameState::new(5, Ruleset::default());
+        let centered = drawn(&state, (66, 26), Anchor::Center);
+        let right = drawn(&state, (66, 26), Anchor::Right);
+
+        assert!(centered.lines()[3].starts_with("          ╔══ HOLD"));
+        assert!(right.lines()[3].starts_with("                    ╔══ HOLD"));
+    }
+
+    #[test]
+    fn moves_to_the_middle_after_a_resize() {
+        let state = GameState::new(5, Ruleset::default());
+        let layout = Layout::new(state.rules());
+        let mut cnv = drawn(&state, (46, 26), Anchor::Center);
+        assert!(cnv.lines()[3].starts_with("╔══ HOLD"));
+
+        cnv.resize((66, 30));
+        assert!(cnv.resized());
+        assert!(cnv.lines().iter().all(|line| line.is_empty()));
+        draw(&state, &layout, &mut cnv, &vec![], &Display::default());
+        assert_eq!(cnv.lines().len(), 30);
+        assert!(cnv.lines()[3].starts_with("          ╔══ HOLD"));
+    }
+
+    #[test]
+    fn help_cuts_long_key_lists_by_character() {
+        let keys = KeyBindings {
+            rotate_right: vec![ Key::Char('e'), Key::Up, Key::Char('ö'), Key::Char('ä') ],
+            hold: vec![ Key::Char('ü') ],
+            ..KeyBindings::default()
+        };
+        let help = controls_help(&keys, &Action::IN_GAME, 20);
+        assert_eq!(help[3], " Rotate R  e/up/ö/ä ");
+        assert_eq!(help[6], " Hold             ü ");
+
+        let help = controls_help(&keys, &Action::IN_GAME, 16);
+        assert_eq!(help[3], " Rotate R e/up/ ");
+        assert!(help.iter().all(|line| line.chars().count() == 16));
+    }
+
+    // Hard drop every tick until the stack tops out, then leave the game over screen
+    fn play_scripted() -> (u64, Vec<String>) {
+        let mut events: Vec<(u64, Key)> = (0..40).map(|tick| (tick, Key::Char(' '))).collect();
+        events.push((50, Key::Enter));
+        events.push((1_000, Key::Backspace)); // Quit, so a game that doesn't end fails
+        let mut inp = Scripted::new(events);
+
+        let mut state = GameState::new(7, Ruleset::default());
+        let mut replay = Replay::new(7, Ruleset::default());
+        let mut cnv = Headless::new((46, 26));
+        let score = play(
+            &mut state, &mut replay, &mut cnv, &mut inp,
+            &vec![], &Display::default(), &KeyBindings::default()
+        );
+        (score, cnv.lines())
+    }
+
+    #[test]
+    fn game_over_goes_on_with_the_start_keys() {
+        let keys = KeyBindings { start: vec![ Key::Char('n') ], ..KeyBindings::default() };
+        let mut events: Vec<(u64, Key)> = (0..40).map(|tick| (tick, Key::Char(' '))).collect();
+        events.push((50, Key::Enter)); // Not bound any more
+        events.push((60, Key::Char('n')));
+        events.push((1_000, Key::Backspace));
+        let mut inp = Scripted::new(events);
+
+        let mut state = GameState::new(7, Ruleset::default());
+        let mut replay = Replay::new(7, Ruleset::default());
+        let mut cnv = Headless::new((46, 26));
+        play(&mut state, &mut replay, &mut cnv, &mut inp, &vec![], &Display::default(), &keys);
+        assert!(!inp.is_done()); // Left before the quit key
+        assert!(cnv.lines().iter().any(|line| line.contains("║   N to continue    ║")));
+    }
+
+    #[test]
+    fn scripted_games_play_out_the_same() {
+        let (score, lines) = play_scripted();
+        assert!(score > 0);
+        assert!(lines.iter().any(|line| line.contains("GAME  OVER")));
+        assert_eq!(play_scripted(), (score, lines));
+    }
+
+    #[test]
+    fn replays_step_one_tick_at_a_time_while_paused() {
+        let mut inp = Scripted::new(vec![
+            (0, Key::Char('p')), (1, Key::Down), (2, Key::Down), (3, Key::Down),
+            (4, Key::Right), (5, Key::Backspace)
+        ]);
+        let mut replay = Replay::new(7, Ruleset::default());
+        replay.finish(100);
+        let mut playback = Playback::new(replay);
+        let mut cnv = Headless::new((46, 26));
+        watch(
+            &mut playback, &mut cnv, &mut inp, &vec![], &Display::default(), &KeyBindings::default()
+        );
+
+        assert_eq!(playback.state().ticks(), 3);
+        assert_eq!(cnv.lines()[2], "            #:00000000000000000007    2x PAUSE");
+    }
+}
@@ -0,0 +1,33 @@
+/*
+ * Author: Dylan Turner
+ * Description: Keep ticks coming at the right rate when the clock's speed changes
+ */
+
+use std::{
+    thread::sleep, time::Duration
+};
+use tetris_cli::clock::Clock;
+
+#[test]
+fn changing_speed_keeps_ticks_already_due() {
+    let mut clock = Clock::new();
+    sleep(Clock::tick_length() * 3);
+    clock.set_speed(8.0);
+    assert!(clock.ticks_due() >= 3);
+}
+
+#[test]
+fn slow_clocks_wait_longer() {
+    let mut clock = Clock::new();
+    clock.set_speed(0.25);
+    assert!(clock.until_next_tick() > Clock::tick_length() * 3);
+
+    clock.set_speed(f64::MIN_POSITIVE);
+    assert_eq!(clock.until_next_tick(), Duration::MAX);
+}
+
+#[test]
+#[should_panic(expected = "Clock speed must be above 0")]
+fn zero_speed_is_rejected() {
+    Clock::new().set_speed(0.0);
+}
@@ -0,0 +1,349 @@
+/*
+ * Author: Dylan Turner
+ * Description: Check the engine's rules on boards set up for them
+ */
+
+use tetris_cli::{
+    game::{
+        BUFFER_HEIGHT, GameState, GRAVITY_UNIT, Input, Ruleset, UpdateEndState
+    }, randomizer::RandomizerKind, tetromino::ShapeType
+};
+
+const LOCK_DELAY_TICKS: u64 = 30; // The default 500ms
+const MAX_LOCK_RESETS: u64 = 15;
+const MAX_TICKS: u64 = 60 * 60;
+const LEVEL_1_GRAVITY: u64 = 1_092;
+const SOFT_DROP_HOLD_TICKS: u64 = 40;
+
+// A game on the given board whose first pieces are shapes. Seeds are tried in order until one
+// deals them, so the same game comes out every time
+fn game_with_rules(shapes: &[ShapeType], rules: Ruleset, rows: &[&str]) -> GameState {
+    (0..).map(|seed| GameState::with_rows(seed, rules.clone(), rows)).find(|state| {
+        let dealt = std::iter::once(state.curr_shape()).chain(state.queue());
+        dealt.zip(shapes).all(|(piece, shape)| piece.shape == *shape)
+    }).unwrap()
+}
+
+fn game_with(shape: ShapeType, rows: &[&str]) -> GameState {
+    game_with_rules(&[ shape ], Ruleset::default(), rows)
+}
+
+// Let the piece fall and lock on its own, which scores nothing for the drop itself.
+// Returns how much the lock scored
+fn fall_and_lock(state: &mut GameState) -> u64 {
+    let score = state.score();
+    fall_until_grounded(state);
+    ticks_until_lock(state, |_| None);
+    state.score() - score
+}
+
+// Turn the piece where it is and let it lock there. Returns how much the lock scored
+fn spin_and_lock(state: &mut GameState, input: Input) -> u64 {
+    let score = state.score();
+    state.handle_input(input);
+    ticks_until_lock(state, |_| None);
+    state.score() - score
+}
+
+fn shift(state: &mut GameState, input: Input, times: usize) {
+    for _ in 0..times {
+        state.handle_input(input);
+    }
+}
+
+// Piece cells in order, to compare without caring how the shape lists them
+fn cells(state: &GameState) -> [(i16, i16); 4] {
+    let mut cells = state.curr_shape_cells();
+    cells.sort();
+    cells
+}
+
+fn grounded(state: &GameState) -> bool {
+    state.ghost_cells() == state.curr_shape_cells()
+}
+
+fn fall_until_grounded(state: &mut GameState) {
+    for _ in 0..MAX_TICKS {
+        if grounded(state) {
+            return;
+        }
+        state.update();
+    }
+    panic!("Piece never landed");
+}
+
+// Updates until the piece locks, with an input before each one. Returns how many it took
+fn ticks_until_lock(state: &mut GameState, mut input: impl FnMut(u64) -> Option<Input>) -> u64 {
+    let blocks = state.blocks().clone();
+    for tick in 0..MAX_TICKS {
+        if let Some(input) = input(tick) {
+            state.handle_input(input);
+        }
+        assert_eq!(state.update(), UpdateEndState::Continue);
+        if *state.blocks() != blocks {
+            return tick + 1;
+        }
+    }
+    panic!("Piece never locked");
+}
+
+#[test]
+fn hard_drop_locks_at_once() {
+    let mut state = game_with(ShapeType::T, &[]);
+    let blocks = state.blocks().clone();
+    state.handle_input(Input::HardDrop);
+    assert_ne!(*state.blocks(), blocks);
+    assert_eq!(state.ticks(), 0);
+    assert!(!grounded(&state)); // The next piece is already up top
+}
+
+#[test]
+fn pieces_lock_after_the_delay_when_left_alone() {
+    let mut state = game_with(ShapeType::T, &[]);
+    fall_until_grounded(&mut state);
+    assert_eq!(ticks_until_lock(&mut state, |_| None), LOCK_DELAY_TICKS + 1);
+}
+
+#[test]
+fn moving_restarts_the_lock_delay_at_most_15_times() {
+    let mut state = game_with(ShapeType::T, &[]);
+    fall_until_grounded(&mut state);
+
+    // Shuffle left and right every tick. Every move restarts the timer until they run out
+    let ticks = ticks_until_lock(&mut state, |tick| Some(if tick % 2 == 0 {
+        Input::Left
+    } else {
+        Input::Right
+    }));
+    assert_eq!(ticks, MAX_LOCK_RESETS + LOCK_DELAY_TICKS);
+}
+
+#[test]
+fn falling_to_a_lower_row_gives_a_full_lock_delay() {
+    let ledge = "####......";
+    let mut state = game_with(ShapeType::T, &[ ledge, ledge, ledge ]);
+    for _ in 0..3 {
+        state.handle_input(Input::Left);
+    }
+    fall_until_grounded(&mut state);
+
+    // Use up the resets on the ledge and let most of the delay run out
+    for tick in 0..MAX_LOCK_RESETS + 1 {
+        state.handle_input(if tick % 2 == 0 { Input::Right } else { Input::Left });
+        state.update();
+    }
+    for _ in 0..LOCK_DELAY_TICKS - 5 {
+        state.update();
+    }
+
+    // Slide off the ledge to the floor
+    for _ in 0..4 {
+        state.handle_input(Input::Right);
+    }
+    assert!(!grounded(&state));
+    fall_until_grounded(&mut state);
+    assert_eq!(ticks_until_lock(&mut state, |_| None), LOCK_DELAY_TICKS + 1);
+}
+
+#[test]
+fn soft_drop_falls_20_times_as_fast_and_scores_a_point_a_row() {
+    let mut state = game_with(ShapeType::T, &[]);
+    let (top, score) = (cells(&state)[0].1, state.score());
+
+    // One press lasts through the wait before the key would start repeating
+    state.handle_input(Input::SoftDrop);
+    for _ in 0..SOFT_DROP_HOLD_TICKS {
+        state.update();
+    }
+    let rows = (cells(&state)[0].1 - top) as u64;
+    assert_eq!(rows, SOFT_DROP_HOLD_TICKS * 20 * LEVEL_1_GRAVITY / GRAVITY_UNIT as u64);
+    assert_eq!(state.score() - score, rows);
+
+    // Then it's back to level 1's row about every second, for nothing
+    let (top, score) = (cells(&state)[0].1, state.score());
+    for _ in 0..SOFT_DROP_HOLD_TICKS {
+        state.update();
+    }
+    assert!(cells(&state)[0].1 - top <= 1);
+    assert_eq!(state.score(), score);
+}
+
+#[test]
+fn full_t_spin_double() {
+    let mut state = game_with(ShapeType::T, &[
+        "#.........", // Overhang makes the third corner
+        "...#######",
+        "#.########"
+    ]);
+    state.handle_input(Input::RotateRight); // Pointing right, then slid over the slot
+    shift(&mut state, Input::Left, 3);
+    fall_until_grounded(&mut state);
+
+    assert_eq!(spin_and_lock(&mut state, Input::RotateRight), 1_200);
+    assert!(state.back_to_back());
+    assert_eq!(state.blocks()[state.blocks().len() - 1][..2], [ 5, -1 ]); // Overhang came down
+}
+
+#[test]
+fn mini_t_spin_single() {
+    // The front corners aren't both filled, so turning into the floor is only a mini
+    let mut state = game_with(ShapeType::T, &[
+        "...#..#...",
+        "####...###"
+    ]);
+    state.handle_input(Input::RotateRight);
+    fall_until_grounded(&mut state);
+
+    assert_eq!(spin_and_lock(&mut state, Input::RotateLeft), 200);
+    assert!(state.back_to_back());
+}
+
+// T slid under a roof, ready to turn with the last kick down into a column
+fn t_spin_triple_setup(bottom_row: &str) -> GameState {
+    let mut state = game_with(ShapeType::T, &[
+        "##........",
+        "#.........",
+        "#.########",
+        "#..#######",
+        bottom_row
+    ]);
+    fall_until_grounded(&mut state);
+    shift(&mut state, Input::Left, 3); // The last one is blocked
+    state
+}
+
+#[test]
+fn t_spin_triple_through_the_last_kick() {
+    let mut state = t_spin_triple_setup("#.########");
+    assert_eq!(cells(&state), [ (1, 16), (2, 15), (2, 16), (3, 16) ]);
+
+    // Every other kick is blocked, so it goes left a cell and down two
+    let score = state.score();
+    state.handle_input(Input::RotateRight);
+    assert_eq!(cells(&state), [ (1, 17), (1, 18), (1, 19), (2, 18) ]);
+    ticks_until_lock(&mut state, |_| None);
+    assert_eq!(state.score() - score, 1_600);
+}
+
+#[test]
+fn kicking_down_to_a_lower_row_gives_a_full_lock_delay() {
+    let mut state = t_spin_triple_setup("#.########");
+
+    // Use up the resets under the roof and let most of the delay run out
+    for tick in 0..MAX_LOCK_RESETS + 1 {
+        state.handle_input(if tick % 2 == 0 { Input::Right } else { Input::Left });
+        state.update();
+    }
+    for _ in 0..LOCK_DELAY_TICKS - 5 {
+        state.update();
+    }
+
+    // The kick takes it down two rows, where it has as long as a piece that fell there
+    state.handle_input(Input::RotateRight);
+    assert_eq!(cells(&state), [ (1, 17), (1, 18), (1, 19), (2, 18) ]);
+    assert_eq!(ticks_until_lock(&mut state, |_| None), LOCK_DELAY_TICKS + 1);
+}
+
+#[test]
+fn last_kick_makes_a_mini_a_full_t_spin() {
+    // One front corner is open, which would be a mini for any other kick
+    let mut state = t_spin_triple_setup("#.#.######");
+    assert_eq!(spin_and_lock(&mut state, Input::RotateRight), 1_200);
+}
+
+#[test]
+fn back_to_back_tetrises_and_combos() {
+    let rules = Ruleset { randomizer: RandomizerKind::Random, ..Ruleset::default() };
+    let well = "#########.";
+    let shapes = [ ShapeType::Line, ShapeType::Line, ShapeType::Line ];
+    let mut state = game_with_rules(&shapes, rules, &[ well; 8 ]);
+
+    // Stand each I up and put it down the well on the right
+    let tetris = |state: &mut GameState| {
+        state.handle_input(Input::RotateRight);
+        shift(state, Input::Right, 4);
+        fall_and_lock(state)
+    };
+    assert_eq!(tetris(&mut state), 800);
+    assert_eq!(state.combo(), Some(0));
+    assert_eq!(tetris(&mut state), 800 * 3 / 2 + 50); // Back-to-back and a combo of 1
+    assert_eq!(state.combo(), Some(1));
+
+    // Nothing cleared ends the combo, but not back-to-back
+    assert_eq!(tetris(&mut state), 0);
+    assert_eq!(state.combo(), None);
+    assert!(state.back_to_back());
+}
+
+#[test]
+fn clears_score_more_at_higher_levels() {
+    let rules = Ruleset { start_level: 3, ..Ruleset::default() };
+    let mut state = game_with_rules(&[ ShapeType::Line ], rules, &[ "###....###" ]);
+    assert_eq!(fall_and_lock(&mut state), 100 * 3);
+    assert_eq!(state.level(), 3);
+}
+
+// Updates until the piece goes down a row. Returns how many it took
+fn ticks_to_fall_a_row(state: &mut GameState) -> u64 {
+    let top = cells(state)[0].1;
+    for tick in 0..MAX_TICKS {
+        state.update();
+        if cells(state)[0].1 != top {
+            return tick + 1;
+        }
+    }
+    panic!("Piece never fell");
+}
+
+#[test]
+fn clearing_enough_lines_goes_up_a_level_and_falls_faster() {
+    let rules = Ruleset { lines_per_level: 4, ..Ruleset::default() };
+    let well = "#########.";
+    let shapes = [ ShapeType::Line, ShapeType::T ];
+    let mut state = game_with_rules(&shapes, rules.clone(), &[ well; 4 ]);
+    let level_1_ticks = ticks_to_fall_a_row(&mut game_with_rules(&shapes, rules, &[]));
+
+    state.handle_input(Input::RotateRight);
+    shift(&mut state, Input::Right, 4);
+    assert_eq!(fall_and_lock(&mut state), 800);
+    assert_eq!(state.lines(), 4);
+    assert_eq!(state.level(), 2);
+    assert!(ticks_to_fall_a_row(&mut state) < level_1_ticks);
+}
+
+#[test]
+fn spawning_on_blocks_is_a_block_out() {
+    let mut rows = vec![ "#########."; 19 ];
+    rows.insert(0, "##....####");
+    let mut state = game_with(ShapeType::T, &rows);
+
+    // The T only gets its bottom into the well. Its top is left where every piece spawns
+    assert_eq!(cells(&state), [ (3, 0), (4, -1), (4, 0), (5, 0) ]);
+    assert_eq!(state.handle_input(Input::HardDrop), UpdateEndState::Lost);
+}
+
+#[test]
+fn locking_in_the_hidden_rows_is_a_lock_out() {
+    let mut state = game_with(ShapeType::T, &[ "#########."; 20 ]);
+
+    // Out of the way of the next spawn, so only the lock itself can end the game
+    shift(&mut state, Input::Left, 3);
+    assert!(cells(&state).iter().all(|(_, y)| *y < 0));
+    assert_eq!(state.handle_input(Input::HardDrop), UpdateEndState::Lost);
+}
+
+#[test]
+fn locking_partly_in_the_hidden_rows_carries_on() {
+    let mut rows = vec![ "#########."; 19 ];
+    rows.insert(0, "......####");
+    let mut state = game_with(ShapeType::T, &rows);
+
+    shift(&mut state, Input::Left, 3);
+    assert_eq!(cells(&state), [ (0, 0), (1, -1), (1, 0), (2, 0) ]);
+    assert_eq!(state.handle_input(Input::HardDrop), UpdateEndState::Continue);
+
+    // Its top is kept in the lowest hidden row and the rest is in row 0
+    let filled = |y: i16, x: usize| state.blocks()[(y + BUFFER_HEIGHT as i16) as usize][x] != -1;
+    assert!(filled(-1, 1) && !filled(-1, 0) && !filled(-1, 2));
+    assert!(filled(0, 0) && filled(0, 1) && filled(0, 2) && !filled(0, 3));
+}
@@ -0,0 +1,193 @@
+/*
+ * Author: Dylan Turner
+ * Description: Keep the high score table in order, throw it out if it's edited by hand and
+ *              bring saves from older versions up to date
+ */
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{
+        Hash, Hasher
+    }
+};
+use tetris_cli::{
+    game::{
+        GameState, Input, Ruleset
+    }, highscore::{
+        HighScore, SaveData, DEFAULT_NAME, HASH_VERSION, MAX_HIGH_SCORES
+    }
+};
+
+const OCT_18_2026: u64 = 1_792_281_600; // Midnight UTC
+
+// A new game's entry with the score swapped out, since only the order matters here
+fn entry(name: &str, score: u64) -> HighScore {
+    let state = GameState::new(0, Ruleset::default());
+    HighScore { score, ..HighScore::ended_at(name, &state, OCT_18_2026) }
+}
+
+fn names(save: &SaveData) -> Vec<&str> {
+    save.high_scores().iter().map(|entry| entry.name.as_str()).collect()
+}
+
+#[test]
+fn table_keeps_the_best_ten_in_order() {
+    let mut save = SaveData::default();
+    assert_eq!(save.insert(entry("a", 500)), Some(0));
+    assert_eq!(save.insert(entry("b", 900)), Some(0));
+    assert_eq!(save.insert(entry("c", 500)), Some(2)); // Ties go below the older score
+    assert_eq!(save.insert(entry("zero", 0)), None);
+    assert_eq!(names(&save), [ "b", "a", "c" ]);
+
+    for score in 1..=10 {
+        save.insert(entry("filler", score * 100));
+    }
+    assert_eq!(save.high_scores().len(), MAX_HIGH_SCORES);
+    assert_eq!(save.best_score(), 1_000);
+    assert_eq!(save.rank(400), None);
+    assert_eq!(save.rank(450), Some(9));
+    assert!(save.high_scores().windows(2).all(|pair| pair[0].score >= pair[1].score));
+}
+
+#[test]
+fn edited_tables_are_thrown_out() {
+    let mut save = SaveData::default();
+    save.insert(entry("a", 500));
+    let text = toml::to_string(&save).unwrap();
+
+    let loaded: SaveData = toml::from_str(&text).unwrap();
+    assert_eq!(names(&loaded), [ "a" ]);
+
+    let edited: SaveData = toml::from_str(&text.replace("500", "5000")).unwrap();
+    assert!(edited.high_scores().is_empty());
+    assert_eq!(edited.best_score(), 0);
+}
+
+const TABLE: &str = "
+[[high_scores]]
+name = 'a'
+score = 500
+lines = 10
+level = 2
+duration_secs = 90
+date = '2026-10-18'
+mode = 'bag7 10x20'
+";
+
+fn default_hash(msg: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    String::from(msg).hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+// A save from before the table, hashed the way it was then
+fn old_save(high_score: u64, salt: &str) -> String {
+    format!(
+        "high_score = {}\nhigh_score_salt = \"{}\"\nhigh_score_hash = \"{}\"\n",
+        high_score, salt, default_hash(&(String::from(salt) + &high_score.to_string()))
+    )
+}
+
+#[test]
+fn hashes_match_the_documented_scheme() {
+    // HMAC-SHA256 keyed with "tetris-cli high scores" over the salt then the table, one
+    // "name|score|lines|level|duration_secs|date|mode" line per entry
+    let hash = "b616d0f69862f99aa9a41422d8b84967d6199e456adad3c6bd0b26ba151eaca8";
+    let text = format!("hash_version = 2\nhigh_scores_salt = '1'\nhigh_scores_hash = '{}'\n", hash);
+    let mut save: SaveData = toml::from_str(&(text + TABLE)).unwrap();
+    assert_eq!(names(&save), [ "a" ]);
+    assert!(!save.migrate());
+}
+
+#[test]
+fn tables_hashed_the_old_way_are_rehashed() {
+    let hash = default_hash("1a|500|10|2|90|2026-10-18|bag7 10x20\n");
+    let text = format!("high_scores_salt = '1'\nhigh_scores_hash = '{}'\n", hash);
+    let mut save: SaveData = toml::from_str(&(text + TABLE)).unwrap();
+    assert!(save.migrate());
+    assert_eq!(names(&save), [ "a" ]);
+
+    let text = toml::to_string(&save).unwrap();
+    assert!(text.starts_with(&format!("hash_version = {}\n", HASH_VERSION)));
+    assert!(!text.contains(&hash));
+    let mut again: SaveData = toml::from_str(&text).unwrap();
+    assert!(!again.migrate());
+    assert_eq!(again.high_scores(), save.high_scores());
+
+    // A bad old hash still loses the table
+    let text = format!("high_scores_salt = '2'\nhigh_scores_hash = '{}'\n", hash);
+    let mut save: SaveData = toml::from_str(&(text + TABLE)).unwrap();
+    assert!(save.migrate());
+    assert!(save.high_scores().is_empty());
+}
+
+#[test]
+fn newer_versions_are_left_alone() {
+    let text = "hash_version = 3\nhigh_scores_salt = '1'\nhigh_scores_hash = 'abc'\n";
+    let mut save: SaveData = toml::from_str(&(String::from(text) + TABLE)).unwrap();
+    assert!(!save.migrate());
+    assert!(save.high_scores().is_empty());
+    assert_eq!(save.rank(1_000), None);
+    assert_eq!(save.insert(entry("b", 1_000)), None);
+
+    let text = toml::to_string(&save).unwrap();
+    assert!(text.contains("hash_version = 3"));
+    assert!(text.contains("high_scores_hash = \"abc\""));
+    assert!(text.contains("name = \"a\""));
+}
+
+#[test]
+fn old_single_scores_start_the_table() {
+    let mut save: SaveData = toml::from_str(&old_save(1234, "5678")).unwrap();
+    assert!(save.migrate());
+    assert_eq!(names(&save), [ DEFAULT_NAME ]);
+    assert_eq!(save.best_score(), 1234);
+
+    // The old fields aren't written back, so this only happens once
+    let text = toml::to_string(&save).unwrap();
+    assert!(!text.contains("high_score ="));
+    let mut again: SaveData = toml::from_str(&text).unwrap();
+    assert!(!again.migrate());
+    assert_eq!(again.high_scores(), save.high_scores());
+
+    let tampered = old_save(1234, "5678").replace("1234", "9999");
+    let mut save: SaveData = toml::from_str(&tampered).unwrap();
+    save.migrate();
+    assert!(save.high_scores().is_empty());
+
+    let mut save: SaveData = toml::from_str("[rules]\nwidth = 8\n").unwrap();
+    save.migrate();
+    assert!(save.high_scores().is_empty());
+    assert_eq!(save.rules.width, 8);
+}
+
+#[test]
+fn entries_come_from_the_game() {
+    let rules = Ruleset { width: 8, ..Ruleset::default() };
+    let mut state = GameState::new(4, rules);
+    state.handle_input(Input::HardDrop);
+    for _ in 0..150 {
+        state.update();
+    }
+
+    let entry = HighScore::new("A much too long name", &state);
+    assert_eq!(entry.name, "A much too");
+    assert_eq!(entry.score, state.score());
+    assert_eq!(entry.duration_secs, 2);
+    assert_eq!(entry.mode, "bag7 8x20");
+    let date: Vec<&str> = entry.date.split('-').collect();
+    assert_eq!(date.iter().map(|part| part.len()).collect::<Vec<_>>(), [ 4, 2, 2 ]);
+    assert!(date[0] >= "2022");
+}
+
+#[test]
+fn entries_are_dated_in_utc() {
+    let state = GameState::new(0, Ruleset::default());
+    let date = |unix_secs| HighScore::ended_at("a", &state, unix_secs).date;
+    assert_eq!(date(0), "1970-01-01");
+    assert_eq!(date(946_598_400 + 86_399), "1999-12-31"); // Last second of the day
+    assert_eq!(date(951_782_400), "2000-02-29"); // Leap day in a year divisible by 400
+    assert_eq!(date(OCT_18_2026), "2026-10-18");
+    assert_eq!(date(4_107_542_400 - 1), "2100-02-28"); // 2100 isn't a leap year
+    assert_eq!(date(4_107_542_400), "2100-03-01");
+}
@@ -0,0 +1,101 @@
+/*
+ * Author: Dylan Turner
+ * Description: Drive whole games from scripted and recorded keys
+ */
+
+use tetris_cli::{
+    config::{
+        Key, KeyBindings
+    }, game::{
+        GameState, Ruleset, UpdateEndState
+    }, input::{
+        KeySource, Recorder, Scripted
+    }
+};
+
+const MAX_TICKS: u64 = 60 * 60 * 10;
+
+// Play a game one tick at a time like a front end would, until it's lost or time runs out
+fn run(source: &mut dyn KeySource, seed: u64) -> GameState {
+    let keys = KeyBindings::default();
+    let mut state = GameState::new(seed, Ruleset::default());
+    for _ in 0..MAX_TICKS {
+        if let Some(input) = source.get_key().and_then(|key| keys.action(key)?.input()) {
+            if state.handle_input(input) == UpdateEndState::Lost {
+                break;
+            }
+        }
+        if state.update() == UpdateEndState::Lost {
+            break;
+        }
+    }
+    state
+}
+
+// Move and turn each piece a different amount then hard drop it
+fn script() -> Vec<(u64, Key)> {
+    let moves = [ Key::Char('a'), Key::Char('d'), Key::Char('q'), Key::Char('e'), Key::Char('w') ];
+    let mut events = Vec::new();
+    for piece in 0..60 {
+        let tick = piece * 30;
+        for step in 0..piece % 5 {
+            events.push((tick + step, moves[(piece + step) as usize % moves.len()]));
+        }
+        events.push((tick + 20, Key::Char(' ')));
+    }
+    events
+}
+
+#[test]
+fn same_keys_same_game() {
+    let first = run(&mut Scripted::new(script()), 42);
+    let second = run(&mut Scripted::new(script()), 42);
+
+    assert!(first.score() > 0);
+    assert_eq!(first.score(), second.score());
+    assert_eq!(first.blocks(), second.blocks());
+    assert_eq!(first.lines(), second.lines());
+}
+
+#[test]
+fn recorded_games_play_back() {
+    let mut recorder = Recorder::new(Scripted::new(script()));
+    let played = run(&mut recorder, 9);
+    assert!(recorder.events().len() > 1);
+
+    let mut playback = Scripted::parse(&recorder.to_text()).unwrap();
+    let replayed = run(&mut playback, 9);
+    assert_eq!(played.score(), replayed.score());
+    assert_eq!(played.blocks(), replayed.blocks());
+}
+
+#[test]
+fn scripted_keys_come_out_on_their_ticks() {
+    let mut source = Scripted::new(vec![ (2, Key::Up), (0, Key::Left), (2, Key::Down) ]);
+    let keys: Vec<Option<Key>> = (0..5).map(|_| source.get_key()).collect();
+    assert_eq!(keys, [ Some(Key::Left), None, Some(Key::Up), Some(Key::Down), None ]);
+    assert!(source.is_done());
+}
+
+#[test]
+fn bad_recordings_are_rejected() {
+    assert!(Scripted::parse("0 space\n5 ctrl+c\n").is_ok());
+    assert_eq!(Scripted::parse("0 space\nx left").err().unwrap(), "Line 2: invalid tick 'x'");
+    assert_eq!(Scripted::parse("3").err().unwrap(), "Line 1: expected '<tick> <key>'");
+    assert!(Scripted::parse("3 nokey").is_err());
+}
+
+#[test]
+fn menu_keys_are_checked_for_conflicts() {
+    assert!(KeyBindings::default().conflicts().is_empty());
+
+    let keys = KeyBindings {
+        hard_drop: vec![ Key::Char(' '), Key::Enter ],
+        scores: vec![ Key::Backspace ],
+        ..KeyBindings::default()
+    };
+    assert_eq!(keys.conflicts(), [
+        "'enter' is bound to both Hard drop and Start",
+        "'backspace' is bound to both Quit and High scores"
+    ]);
+}
@@ -0,0 +1,106 @@
+/*
+ * Author: Dylan Turner
+ * Description: Record games to replays and check they play back the same
+ */
+
+use std::{
+    env::temp_dir, fs
+};
+use tetris_cli::{
+    game::{
+        GameState, Input, Ruleset, UpdateEndState
+    }, replay::{
+        Playback, Replay
+    }
+};
+
+// Drop pieces in different columns, holding now and then, until the stack tops out
+fn record(seed: u64, rules: Ruleset) -> (GameState, Replay) {
+    let moves = [ Input::Left, Input::Right, Input::RotateLeft, Input::Hold, Input::SoftDrop ];
+    let mut state = GameState::new(seed, rules.clone());
+    let mut replay = Replay::new(seed, rules);
+    'game: for piece in 0..1_000 {
+        for tick in 0..20 {
+            let input = match tick {
+                19 => Some(Input::HardDrop),
+                _ if tick < piece % 7 => Some(moves[(piece + tick) % moves.len()]),
+                _ => None
+            };
+            if let Some(input) = input {
+                replay.record(state.ticks(), input);
+                if state.handle_input(input) == UpdateEndState::Lost {
+                    break 'game;
+                }
+            }
+            if state.update() == UpdateEndState::Lost {
+                break 'game;
+            }
+        }
+    }
+    replay.finish(state.ticks());
+    (state, replay)
+}
+
+fn play_back(replay: Replay) -> Playback {
+    let mut playback = Playback::new(replay);
+    while !playback.is_over() {
+        playback.step();
+    }
+    playback
+}
+
+#[test]
+fn replays_play_back_the_same_game() {
+    let (played, replay) = record(11, Ruleset::default());
+    assert!(played.score() > 0);
+
+    let playback = play_back(replay);
+    let replayed = playback.state();
+    assert_eq!(replayed.score(), played.score());
+    assert_eq!(replayed.blocks(), played.blocks());
+    assert_eq!(replayed.ticks(), played.ticks());
+}
+
+#[test]
+fn replays_survive_a_save_and_load() {
+    let rules = Ruleset { width: 8, start_level: 5, ..Ruleset::default() };
+    let (played, replay) = record(u64::MAX, rules);
+    let path = temp_dir().join("tetris-cli-replay-test.toml");
+    let path = path.to_str().unwrap();
+
+    replay.save(path).unwrap();
+    let loaded = Replay::load(path).unwrap();
+    fs::remove_file(path).unwrap();
+    assert_eq!(loaded, replay);
+    assert_eq!(play_back(loaded).state().score(), played.score());
+}
+
+#[test]
+fn replays_stop_where_the_player_quit() {
+    let mut replay = Replay::new(3, Ruleset::default());
+    replay.record(0, Input::HardDrop);
+    replay.finish(30);
+
+    let mut playback = Playback::new(replay);
+    for _ in 0..30 {
+        playback.step();
+    }
+    assert!(!playback.is_over());
+    playback.step();
+    assert!(playback.is_over());
+    assert_eq!(playback.state().ticks(), 30);
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let path = temp_dir().join("tetris-cli-old-replay-test.toml");
+    let path = path.to_str().unwrap();
+    let mut replay = Replay::new(3, Ruleset::default());
+    replay.version = 0;
+    replay.save(path).unwrap();
+
+    let err = Replay::load(path).err().unwrap();
+    fs::remove_file(path).unwrap();
+    assert!(err.contains("version 0 replay"));
+    assert!(Replay::load("/nonexistent/replay.toml").is_err());
+}
@@ -0,0 +1,186 @@
+/*
+ * Author: Dylan Turner
+ * Description: Check shapes turn the SRS way and try the right wall kicks
+ */
+
+use tetris_cli::{
+    game::{
+        Dir, GameState, Input, Ruleset
+    }, tetromino::{
+        Rotation, ShapeType, Tetromino
+    }
+};
+
+const MAX_TICKS: u64 = 60 * 60;
+
+fn sorted(mut cells: [(i16, i16); 4]) -> [(i16, i16); 4] {
+    cells.sort();
+    cells
+}
+
+// A game whose first piece is shape. Seeds are tried in order, so it's the same game every time
+fn game_with(shape: ShapeType) -> GameState {
+    (0..).map(|seed| GameState::new(seed, Ruleset::default()))
+        .find(|state| state.curr_shape().shape == shape).unwrap()
+}
+
+fn shift(state: &mut GameState, input: Input, times: usize) {
+    for _ in 0..times {
+        state.handle_input(input);
+    }
+}
+
+// Let the piece fall until it's on the floor, without locking it
+fn land(state: &mut GameState) {
+    for _ in 0..MAX_TICKS {
+        if state.ghost_cells() == state.curr_shape_cells() {
+            return;
+        }
+        state.update();
+    }
+    panic!("Piece never landed");
+}
+
+#[test]
+fn four_turns_come_back_around() {
+    for shape in ShapeType::ALL {
+        for dir in [ Dir::Right, Dir::Left ] {
+            let start = Tetromino::new(shape);
+            let mut piece = start;
+            let mut states = Vec::new();
+            for _ in 0..4 {
+                piece.rotate(dir);
+                states.push(piece.rotation);
+                assert_eq!(piece.pos, start.pos, "{:?} moved turning", shape);
+            }
+            assert_eq!(sorted(piece.coords), sorted(start.coords), "{:?} {:?}", shape, dir);
+
+            let order = match dir {
+                Dir::Right => [ Rotation::Right, Rotation::Two, Rotation::Left, Rotation::Spawn ],
+                _ => [ Rotation::Left, Rotation::Two, Rotation::Right, Rotation::Spawn ]
+            };
+            assert_eq!(states, order);
+        }
+    }
+}
+
+#[test]
+fn turning_back_undoes_a_turn() {
+    for shape in ShapeType::ALL {
+        let mut piece = Tetromino::new(shape);
+        for _ in 0..4 {
+            let before = piece;
+            piece.rotate(Dir::Right);
+            piece.rotate(Dir::Left);
+            assert_eq!(sorted(piece.coords), sorted(before.coords), "{:?}", shape);
+            assert_eq!(piece.rotation, before.rotation);
+            piece.rotate(Dir::Right);
+        }
+    }
+}
+
+#[test]
+fn shapes_turn_around_their_srs_centers() {
+    // T turns around its middle cell
+    let mut t = Tetromino::new(ShapeType::T);
+    t.rotate(Dir::Right);
+    assert_eq!(sorted(t.coords), [ (0, -1), (0, 0), (0, 1), (1, 0) ]);
+
+    // I stands up in the column right of center, covering the row above to two below
+    let mut line = Tetromino::new(ShapeType::Line);
+    line.rotate(Dir::Right);
+    assert_eq!(sorted(line.coords), [ (1, -1), (1, 0), (1, 1), (1, 2) ]);
+    line.rotate(Dir::Right);
+    assert_eq!(sorted(line.coords), [ (-1, 1), (0, 1), (1, 1), (2, 1) ]);
+
+    // O doesn't move at all
+    let mut square = Tetromino::new(ShapeType::Square);
+    square.rotate(Dir::Left);
+    assert_eq!(sorted(square.coords), sorted(Tetromino::new(ShapeType::Square).coords));
+}
+
+// Kicks for each turn, from the SRS tables with y flipped so down is positive
+#[test]
+fn kicks_come_from_the_row_for_the_turn() {
+    let jlstz = [
+        (Rotation::Spawn, Dir::Right, [ (0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2) ]),
+        (Rotation::Spawn, Dir::Left, [ (0, 0), (1, 0), (1, -1), (0, 2), (1, 2) ]),
+        (Rotation::Right, Dir::Right, [ (0, 0), (1, 0), (1, 1), (0, -2), (1, -2) ]),
+        (Rotation::Right, Dir::Left, [ (0, 0), (1, 0), (1, 1), (0, -2), (1, -2) ]),
+        (Rotation::Two, Dir::Right, [ (0, 0), (1, 0), (1, -1), (0, 2), (1, 2) ]),
+        (Rotation::Two, Dir::Left, [ (0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2) ]),
+        (Rotation::Left, Dir::Right, [ (0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2) ]),
+        (Rotation::Left, Dir::Left, [ (0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2) ])
+    ];
+    let i = [
+        (Rotation::Spawn, Dir::Right, [ (0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2) ]),
+        (Rotation::Spawn, Dir::Left, [ (0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1) ]),
+        (Rotation::Right, Dir::Right, [ (0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1) ]),
+        (Rotation::Right, Dir::Left, [ (0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2) ]),
+        (Rotation::Two, Dir::Right, [ (0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2) ]),
+        (Rotation::Two, Dir::Left, [ (0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1) ]),
+        (Rotation::Left, Dir::Right, [ (0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1) ]),
+        (Rotation::Left, Dir::Left, [ (0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2) ])
+    ];
+
+    for shape in ShapeType::ALL {
+        let table = match shape {
+            ShapeType::Line => &i,
+            _ => &jlstz
+        };
+        for (rotation, dir, kicks) in table {
+            let piece = Tetromino { rotation: *rotation, ..Tetromino::new(shape) };
+            let expected: &[(i16, i16)] = match shape {
+                ShapeType::Square => &[ (0, 0) ],
+                _ => kicks
+            };
+            assert_eq!(piece.kicks(*dir), expected, "{:?} {:?} {:?}", shape, rotation, dir);
+        }
+    }
+}
+
+#[test]
+fn t_kicks_off_the_wall() {
+    let mut state = game_with(ShapeType::T);
+    state.handle_input(Input::RotateRight);
+    shift(&mut state, Input::Left, 5); // Stem against the left wall
+    assert_eq!(sorted(state.curr_shape_cells()), [ (0, -1), (0, 0), (0, 1), (1, 0) ]);
+
+    // Flat again would stick out of the wall, so it's pushed right a cell
+    state.handle_input(Input::RotateLeft);
+    assert_eq!(sorted(state.curr_shape_cells()), [ (0, 0), (1, -1), (1, 0), (2, 0) ]);
+}
+
+#[test]
+fn t_kicks_up_off_the_floor() {
+    let mut state = game_with(ShapeType::T);
+    land(&mut state);
+    assert_eq!(sorted(state.curr_shape_cells()), [ (3, 19), (4, 18), (4, 19), (5, 19) ]);
+
+    // Standing up in place would go through the floor, so it goes left and up a row
+    state.handle_input(Input::RotateRight);
+    assert_eq!(sorted(state.curr_shape_cells()), [ (3, 17), (3, 18), (3, 19), (4, 18) ]);
+}
+
+#[test]
+fn i_kicks_off_the_wall() {
+    let mut state = game_with(ShapeType::Line);
+    state.handle_input(Input::RotateRight);
+    shift(&mut state, Input::Right, 5); // Standing in the last column
+    assert_eq!(sorted(state.curr_shape_cells()), [ (9, -1), (9, 0), (9, 1), (9, 2) ]);
+
+    // Laid flat it would reach past the wall, so it goes left a cell
+    state.handle_input(Input::RotateRight);
+    assert_eq!(sorted(state.curr_shape_cells()), [ (6, 1), (7, 1), (8, 1), (9, 1) ]);
+}
+
+#[test]
+fn i_kicks_up_off_the_floor() {
+    let mut state = game_with(ShapeType::Line);
+    land(&mut state);
+    assert_eq!(sorted(state.curr_shape_cells()), [ (3, 19), (4, 19), (5, 19), (6, 19) ]);
+
+    // Every kick but the last leaves it in the floor
+    state.handle_input(Input::RotateRight);
+    assert_eq!(sorted(state.curr_shape_cells()), [ (6, 16), (6, 17), (6, 18), (6, 19) ]);
+}